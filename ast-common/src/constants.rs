@@ -1,113 +1,476 @@
+//! Game constants.
+//!
+//! Tables of the named constants understood by the game: logic types, slot logic types, batch
+//! and reagent modes, and the hashes of common prefabs. Lookups are case-insensitive and yield
+//! the canonical (in-game) spelling of a name.
 use std::collections::HashMap;
+use std::fmt;
 
 use lazy_static::lazy_static;
-use maplit::hashmap;
-//
+
+/// A table of named game constants.
+#[derive(Clone, Debug)]
+pub struct ConstTable {
+    kind: &'static str,
+    entries: Vec<(&'static str, i64)>,
+    // Lowercased name (or alternative name) to entry index
+    lookup: HashMap<String, usize>,
+}
+
+impl ConstTable {
+    fn new(kind: &'static str, entries: Vec<(&'static str, i64)>) -> Self {
+        let lookup = entries
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (name.to_lowercase(), i))
+            .collect();
+        Self {
+            kind,
+            entries,
+            lookup,
+        }
+    }
+
+    fn with_alts(mut self, alts: &[(&'static str, &'static str)]) -> Self {
+        for (alt, name) in alts.iter() {
+            let i = self.lookup[&name.to_lowercase()];
+            self.lookup.insert(alt.to_lowercase(), i);
+        }
+        self
+    }
+
+    /// What kind of constant this table holds (e.g. "logic type").
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    /// Get the canonical name and value of a constant.
+    pub fn get(&self, name: &str) -> Option<(&'static str, i64)> {
+        self.lookup
+            .get(&name.to_lowercase())
+            .map(|i| self.entries[*i])
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.lookup.contains_key(&name.to_lowercase())
+    }
+
+    pub fn canonical(&self, name: &str) -> Option<&'static str> {
+        self.get(name).map(|(name, _)| name)
+    }
+
+    /// Get the canonical spelling of a name, or the name itself if it isn't in the table.
+    pub fn canonicalize(&self, name: String) -> String {
+        self.canonical(&name).map(str::to_owned).unwrap_or(name)
+    }
+
+    pub fn value(&self, name: &str) -> Option<i64> {
+        self.get(name).map(|(_, value)| value)
+    }
+
+    /// Get the canonical name of a value.
+    pub fn name_of(&self, value: i64) -> Option<&'static str> {
+        self.entries
+            .iter()
+            .find_map(|(name, v)| (*v == value).then_some(*name))
+    }
+
+    /// Iterate over the canonical names and values.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, i64)> + '_ {
+        self.entries.iter().copied()
+    }
+
+    /// Iterate over the lowercased names (including alternative names) and values.
+    pub fn iter_lowercase(&self) -> impl Iterator<Item = (&str, i64)> + '_ {
+        self.lookup
+            .iter()
+            .map(move |(key, i)| (key.as_str(), self.entries[*i].1))
+    }
+
+    /// Get the canonical spelling of a name, or an error suggesting the closest name.
+    pub fn try_canonical(&self, name: &str) -> Result<&'static str, ConstError> {
+        self.canonical(name)
+            .ok_or_else(|| ConstError::unknown(self.kind, name, self.suggest(name)))
+    }
+
+    /// Get the name (or alternative name) closest to a misspelled name by edit distance, if any
+    /// is close enough to plausibly be what was meant.
+    pub fn suggest(&self, name: &str) -> Option<&'static str> {
+        let name = name.to_lowercase();
+        let max_dist = name.chars().count().max(3) / 3;
+        self.lookup
+            .iter()
+            .map(|(key, i)| (edit_distance(&name, key), self.entries[*i].0))
+            .filter(|(dist, _)| *dist <= max_dist)
+            .min()
+            .map(|(_, name)| name)
+    }
+}
+
+/// A name that is not in a constant table.
+#[derive(Clone, Debug)]
+pub struct ConstError {
+    pub kind: &'static str,
+    pub name: String,
+    pub suggestion: Option<&'static str>,
+    // The kind of table the name does belong to, if it's in the wrong one
+    pub found_kind: Option<&'static str>,
+}
+
+impl ConstError {
+    pub fn unknown(kind: &'static str, name: &str, suggestion: Option<&'static str>) -> Self {
+        Self {
+            kind,
+            name: name.to_owned(),
+            suggestion,
+            found_kind: None,
+        }
+    }
+
+    pub fn wrong_kind(kind: &'static str, name: &str, found_kind: &'static str) -> Self {
+        Self {
+            kind,
+            name: name.to_owned(),
+            suggestion: None,
+            found_kind: Some(found_kind),
+        }
+    }
+}
+
+impl fmt::Display for ConstError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(found_kind) = self.found_kind {
+            write!(
+                f,
+                "'{}' is a {}, expected a {}",
+                self.name, found_kind, self.kind
+            )?;
+        } else {
+            write!(f, "Unknown {} '{}'", self.kind, self.name)?;
+        }
+        if let Some(suggestion) = self.suggestion {
+            write!(f, " (did you mean '{}'?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConstError {}
+
+/// Get the canonical spelling of a logic type.
+///
+/// Slot logic types are rejected, since they can only be used with slot reads.
+pub fn logic_type(name: &str) -> Result<&'static str, ConstError> {
+    LOGIC_TYPES.try_canonical(name).map_err(|e| {
+        if SLOT_LOGIC_TYPES.contains(name) {
+            ConstError::wrong_kind(LOGIC_TYPES.kind, name, SLOT_LOGIC_TYPES.kind)
+        } else {
+            e
+        }
+    })
+}
+
+/// Get the canonical spelling of a slot logic type.
+pub fn slot_logic_type(name: &str) -> Result<&'static str, ConstError> {
+    SLOT_LOGIC_TYPES.try_canonical(name).map_err(|e| {
+        if LOGIC_TYPES.contains(name) {
+            ConstError::wrong_kind(SLOT_LOGIC_TYPES.kind, name, LOGIC_TYPES.kind)
+        } else {
+            e
+        }
+    })
+}
+
+// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let sub = diag + (ca != *cb) as usize;
+            diag = row[j + 1];
+            row[j + 1] = sub.min(row[j] + 1).min(diag + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Hash a string the same way as the in-game `HASH("...")` function (a signed CRC-32).
+pub fn hash(s: &str) -> i32 {
+    let mut crc = !0_u32;
+    for b in s.bytes() {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc as i32
+}
+
+#[rustfmt::skip]
+const LOGIC_TYPE_ENTRIES: &[(&str, i64)] = &[
+    ("None",                      0),
+    ("Power",                     1),
+    ("Open",                      2),
+    ("Mode",                      3),
+    ("Error",                     4),
+    ("Pressure",                  5),
+    ("Temperature",               6),
+    ("PressureExternal",          7),
+    ("PressureInternal",          8),
+    ("Activate",                  9),
+    ("Lock",                      10),
+    ("Charge",                    11),
+    ("Setting",                   12),
+    ("Reagents",                  13),
+    ("RatioOxygen",               14),
+    ("RatioCarbonDioxide",        15),
+    ("RatioNitrogen",             16),
+    ("RatioPollutant",            17),
+    ("RatioVolatiles",            18),
+    ("RatioWater",                19),
+    ("Horizontal",                20),
+    ("Vertical",                  21),
+    ("SolarAngle",                22),
+    ("Maximum",                   23),
+    ("Ratio",                     24),
+    ("PowerPotential",            25),
+    ("PowerActual",               26),
+    ("Quantity",                  27),
+    ("On",                        28),
+    ("ImportQuantity",            29),
+    ("ImportSlotOccupant",        30),
+    ("ExportQuantity",            31),
+    ("ExportSlotOccupant",        32),
+    ("RequiredPower",             33),
+    ("HorizontalRatio",           34),
+    ("VerticalRatio",             35),
+    ("PowerRequired",             36),
+    ("Idle",                      37),
+    ("Color",                     38),
+    ("ElevatorSpeed",             39),
+    ("ElevatorLevel",             40),
+    ("RecipeHash",                41),
+    ("ExportSlotHash",            42),
+    ("ImportSlotHash",            43),
+    ("PlantHealth1",              44),
+    ("PlantHealth2",              45),
+    ("PlantHealth3",              46),
+    ("PlantHealth4",              47),
+    ("PlantGrowth1",              48),
+    ("PlantGrowth2",              49),
+    ("PlantGrowth3",              50),
+    ("PlantGrowth4",              51),
+    ("PlantEfficiency1",          52),
+    ("PlantEfficiency2",          53),
+    ("PlantEfficiency3",          54),
+    ("PlantEfficiency4",          55),
+    ("PlantHash1",                56),
+    ("PlantHash2",                57),
+    ("PlantHash3",                58),
+    ("PlantHash4",                59),
+    ("RequestHash",               60),
+    ("CompletionRatio",           61),
+    ("ClearMemory",               62),
+    ("ExportCount",               63),
+    ("ImportCount",               64),
+    ("PowerGeneration",           65),
+    ("TotalMoles",                66),
+    ("Volume",                    67),
+    ("Plant",                     68),
+    ("Harvest",                   69),
+    ("Output",                    70),
+    ("PressureSetting",           71),
+    ("TemperatureSetting",        72),
+    ("TemperatureExternal",       73),
+    ("Filtration",                74),
+    ("AirRelease",                75),
+    ("PositionX",                 76),
+    ("PositionY",                 77),
+    ("PositionZ",                 78),
+    ("VelocityMagnitude",         79),
+    ("VelocityRelativeX",         80),
+    ("VelocityRelativeY",         81),
+    ("VelocityRelativeZ",         82),
+    ("RatioNitrousOxide",         83),
+    ("PrefabHash",                84),
+    ("ForceWrite",                85),
+    ("SignalStrength",            86),
+    ("SignalID",                  87),
+    ("TargetX",                   88),
+    ("TargetY",                   89),
+    ("TargetZ",                   90),
+    ("SettingInput",              91),
+    ("SettingOutput",             92),
+    ("CurrentResearchPodType",    93),
+    ("ManualResearchRequiredPod", 94),
+    ("MineablesInVicinity",       95),
+    ("MineablesInQueue",          96),
+    ("NextWeatherEventTime",      97),
+    ("Combustion",                98),
+    ("Fuel",                      99),
+    ("ReturnFuelCost",            100),
+    ("CollectableGoods",          101),
+    ("Time",                      102),
+    ("Bpm",                       103),
+];
+
+#[rustfmt::skip]
+const SLOT_LOGIC_TYPE_ENTRIES: &[(&str, i64)] = &[
+    ("None",          0),
+    ("Occupied",      1),
+    ("OccupantHash",  2),
+    ("Quantity",      3),
+    ("Damage",        4),
+    ("Efficiency",    5),
+    ("Health",        6),
+    ("Growth",        7),
+    ("Pressure",      8),
+    ("Temperature",   9),
+    ("Charge",        10),
+    ("ChargeRatio",   11),
+    ("Class",         12),
+    ("PressureWaste", 13),
+    ("PressureAir",   14),
+    ("MaxQuantity",   15),
+    ("Mature",        16),
+    ("PrefabHash",    17),
+    ("Seeding",       18),
+    ("LineNumber",    19),
+    ("Volume",        20),
+    ("Open",          21),
+    ("On",            22),
+    ("Lock",          23),
+    ("SortingClass",  24),
+    ("FilterType",    25),
+    ("ReferenceId",   26),
+];
+
+const PREFAB_NAMES: &[&str] = &[
+    "StructureActiveVent",
+    "StructureAdvancedFurnace",
+    "StructureAirConditioner",
+    "StructureArcFurnace",
+    "StructureAutolathe",
+    "StructureBackPressureRegulator",
+    "StructureBattery",
+    "StructureBatteryLarge",
+    "StructureCentrifuge",
+    "StructureConsoleLED5",
+    "StructureDaylightSensor",
+    "StructureDigitalValve",
+    "StructureElectronicsPrinter",
+    "StructureFiltration",
+    "StructureFurnace",
+    "StructureGasSensor",
+    "StructureGasTankStorage",
+    "StructureGrowLight",
+    "StructureHydroponicsStation",
+    "StructureLiquidPipeAnalyzer",
+    "StructureMotionSensor",
+    "StructureOccupancySensor",
+    "StructurePipeAnalysizer",
+    "StructurePressureRegulator",
+    "StructureRecycler",
+    "StructureSolarPanel",
+    "StructureSolarPanelDual",
+    "StructureSolarPanelDualReinforced",
+    "StructureSolarPanelFlat",
+    "StructureSolarPanelFlatReinforced",
+    "StructureSolarPanelReinforced",
+    "StructureSorter",
+    "StructureStacker",
+    "StructureTankBig",
+    "StructureTankSmall",
+    "StructureTurboVolumePump",
+    "StructureVendingMachine",
+    "StructureVolumePump",
+    "StructureWallCooler",
+    "StructureWallHeater",
+    "StructureWallLight",
+];
+
 lazy_static! {
-    static ref LOGIC: HashMap<&'static str, usize> = hashmap! {
-        "None":                      0,
-        "Power":                     1,
-        "Open":                      2,
-        "Mode":                      3,
-        "Error":                     4,
-        "Pressure":                  5,
-        "Temperature":               6,
-        "PressureExternal":          7,
-        "PressureInternal":          8,
-        "Activate":                  9,
-        "Lock":                      10,
-        "Charge":                    11,
-        "Setting":                   12,
-        "Reagents":                  13,
-        "RatioOxygen":               14,
-        "RatioCarbonDioxide":        15,
-        "RatioNitrogen":             16,
-        "RatioPollutant":            17,
-        "RatioVolatiles":            18,
-        "RatioWater":                19,
-        "Horizontal":                20,
-        "Vertical":                  21,
-        "SolarAngle":                22,
-        "Maximum":                   23,
-        "Ratio":                     24,
-        "PowerPotential":            25,
-        "PowerActual":               26,
-        "Quantity":                  27,
-        "On":                        28,
-        "ImportQuantity":            29,
-        "ImportSlotOccupant":        30,
-        "ExportQuantity":            31,
-        "ExportSlotOccupant":        32,
-        "RequiredPower":             33,
-        "HorizontalRatio":           34,
-        "VerticalRatio":             35,
-        "PowerRequired":             36,
-        "Idle":                      37,
-        "Color":                     38,
-        "ElevatorSpeed":             39,
-        "ElevatorLevel":             40,
-        "RecipeHash":                41,
-        "ExportSlotHash":            42,
-        "ImportSlotHash":            43,
-        "PlantHealth1":              44,
-        "PlantHealth2":              45,
-        "PlantHealth3":              46,
-        "PlantHealth4":              47,
-        "PlantGrowth1":              48,
-        "PlantGrowth2":              49,
-        "PlantGrowth3":              50,
-        "PlantGrowth4":              51,
-        "PlantEfficiency1":          52,
-        "PlantEfficiency2":          53,
-        "PlantEfficiency3":          54,
-        "PlantEfficiency4":          55,
-        "PlantHash1":                56,
-        "PlantHash2":                57,
-        "PlantHash3":                58,
-        "PlantHash4":                59,
-        "RequestHash":               60,
-        "CompletionRatio":           61,
-        "ClearMemory":               62,
-        "ExportCount":               63,
-        "ImportCount":               64,
-        "PowerGeneration":           65,
-        "TotalMoles":                66,
-        "Volume":                    67,
-        "Plant":                     68,
-        "Harvest":                   69,
-        "Output":                    70,
-        "PressureSetting":           71,
-        "TemperatureSetting":        72,
-        "TemperatureExternal":       73,
-        "Filtration":                74,
-        "AirRelease":                75,
-        "PositionX":                 76,
-        "PositionY":                 77,
-        "PositionZ":                 78,
-        "VelocityMagnitude":         79,
-        "VelocityRelativeX":         80,
-        "VelocityRelativeY":         81,
-        "VelocityRelativeZ":         82,
-        "RatioNitrousOxide":         83,
-        "PrefabHash":                84,
-        "ForceWrite":                85,
-        "SignalStrength":            86,
-        "SignalID":                  87,
-        "TargetX":                   88,
-        "TargetY":                   89,
-        "TargetZ":                   90,
-        "SettingInput":              91,
-        "SettingOutput":             92,
-        "CurrentResearchPodType":    93,
-        "ManualResearchRequiredPod": 94,
-        "MineablesInVicinity":       95,
-        "MineablesInQueue":          96,
-        "NextWeatherEventTime":      97,
-        "Combustion":                98,
-        "Fuel":                      99,
-        "ReturnFuelCost":            100,
-        "CollectableGoods":          101,
-        "Time":                      102,
-        "Bpm":                       103
-    };
+    pub static ref LOGIC_TYPES: ConstTable =
+        ConstTable::new("logic type", LOGIC_TYPE_ENTRIES.to_vec());
+
+    pub static ref SLOT_LOGIC_TYPES: ConstTable =
+        ConstTable::new("slot logic type", SLOT_LOGIC_TYPE_ENTRIES.to_vec());
+
+    #[rustfmt::skip]
+    pub static ref BATCH_MODES: ConstTable = ConstTable::new("batch mode", vec![
+        ("Average", 0),
+        ("Sum",     1),
+        ("Minimum", 2),
+        ("Maximum", 3),
+    ])
+    .with_alts(&[("Avg", "Average"), ("Min", "Minimum"), ("Max", "Maximum")]);
+
+    #[rustfmt::skip]
+    pub static ref REAGENT_MODES: ConstTable = ConstTable::new("reagent mode", vec![
+        ("Contents", 0),
+        ("Required", 1),
+        ("Recipe",   2),
+    ])
+    .with_alts(&[("Cns", "Contents"), ("Rqd", "Required"), ("Rcp", "Recipe")]);
+
+    pub static ref PREFABS: ConstTable = ConstTable::new(
+        "prefab",
+        PREFAB_NAMES
+            .iter()
+            .map(|name| (*name, hash(name) as i64))
+            .collect(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_crc32() {
+        // The standard CRC-32 check value, as a signed integer
+        assert_eq!(hash("123456789"), 0xCBF4_3926_u32 as i32);
+        assert_eq!(hash(""), 0);
+        assert_eq!(hash("StructureGasSensor"), -1252983604);
+        assert_eq!(hash("StructureBattery"), -400115994);
+        assert_eq!(
+            PREFABS.value("structurebattery"),
+            Some(hash("StructureBattery") as i64)
+        );
+    }
+
+    #[test]
+    fn suggest_close_names() {
+        assert_eq!(LOGIC_TYPES.suggest("Temprature"), Some("Temperature"));
+        assert_eq!(LOGIC_TYPES.suggest("PRESURE"), Some("Pressure"));
+        // Alternative names suggest their canonical name
+        assert_eq!(BATCH_MODES.suggest("avgg"), Some("Average"));
+        assert_eq!(BATCH_MODES.suggest("Mx"), Some("Maximum"));
+    }
+
+    #[test]
+    fn suggest_nothing_when_far() {
+        assert_eq!(LOGIC_TYPES.suggest("Xyzzy"), None);
+        // Short names allow only a single edit
+        assert_eq!(BATCH_MODES.suggest("Sxq"), None);
+        assert_eq!(BATCH_MODES.suggest("Sm"), Some("Sum"));
+    }
+
+    #[test]
+    fn suggest_in_unknown_error() {
+        let e = LOGIC_TYPES.try_canonical("Setings").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Unknown logic type 'Setings' (did you mean 'Setting'?)"
+        );
+        let e = logic_type("Occupied").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "'Occupied' is a slot logic type, expected a logic type"
+        );
+    }
 }
@@ -1,115 +1,93 @@
-#![feature(stmt_expr_attributes)]
-#![allow(unused_imports)]
-use std::{fmt, fmt::Display};
+//! AST primitives shared by the MIPS and MYPS parsers.
+//!
+//! The types here know how to parse and display themselves, but not which grammar rule they come
+//! from; each parser implements `AstNode` for them over its own rules and error type.
+use std::fmt;
 
-use pest::iterators::Pair;
-use pest::{Parser, RuleType};
+pub mod constants;
 
-use ast_traits::{AstError, AstNode};
-// use crate::ast::{MipsNode, RegBase};
-// use crate::{Mips, MipsError, MipsParser, MipsResult, Pair, Rule};
+mod mode;
+pub use mode::{BatchMode, ModeRepr, ReagentMode};
 
-mod constants;
+mod logic_type;
+pub use logic_type::{LogicType, SlotLogicType};
 
-// pub trait AstCommonRule: RuleType {
-//     // Note that these rules aren't used for anything.
-//     // Rust doesn't have associated constant pattern matching, making these useless.
-//     const RULE_BATCH_MODE: Self;
-//     const RULE_REAGENT_MODE: Self;
-// }
-//
-// #[derive(Copy, Clone, Debug)]
-// pub enum ModeRepr {
-//     Int,
-//     Str,
-// }
-//
-// #[inline]
-// fn get_repr(int_s: &'static str, pair_s: &str) -> ModeRepr {
-//     if int_s == pair_s {
-//         ModeRepr::Int
-//     } else {
-//         ModeRepr::Str
-//     }
-// }
-//
-// #[inline]
-// fn repr_disp(repr: &ModeRepr, int_repr: &'static str, str_repr: &'static str) -> &'static str {
-//     if matches!(repr, ModeRepr::Int) {
-//         int_repr
-//     } else {
-//         str_repr
-//     }
-// }
-//
-// #[derive(Copy, Clone, Debug)]
-// pub enum BatchMode {
-//     Avg(ModeRepr),
-//     Sum(ModeRepr),
-//     Min(ModeRepr),
-//     Max(ModeRepr),
-// }
-//
-// impl<'i, R: AstCommonRule, P: Parser<R>, E: AstError<R>> AstNode<'i, R, P, E> for BatchMode {
-//     type Output = Self;
-//
-//     const RULE: R = R::RULE_BATCH_MODE;
-//
-//     fn try_from_pair(pair: Pair<'i, R>) -> Result<Self::Output, E> {
-//         println!("try BatchMode from {:?}", pair);
-//         let s = pair.as_str();
-//         #[rustfmt::skip]
-//         match s.to_lowercase().as_str() {
-//             "0" | "average" | "avg" => Ok(Self::Avg(get_repr("0", s))),
-//             "1" | "sum"             => Ok(Self::Sum(get_repr("1", s))),
-//             "2" | "minimum" | "min" => Ok(Self::Min(get_repr("2", s))),
-//             "3" | "maximum" | "max" => Ok(Self::Max(get_repr("3", s))),
-//             _ => Err(E::pair_wrong_rule("a batch mode", pair)),
-//         }
-//     }
-// }
-//
-// impl Display for BatchMode {
-//     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//         match self {
-//             Self::Avg(mode_repr) => write!(f, "{}", repr_disp(mode_repr, "0", "Average")),
-//             Self::Sum(mode_repr) => write!(f, "{}", repr_disp(mode_repr, "1", "Sum")),
-//             Self::Min(mode_repr) => write!(f, "{}", repr_disp(mode_repr, "2", "Min")),
-//             Self::Max(mode_repr) => write!(f, "{}", repr_disp(mode_repr, "3", "Max")),
-//         }
-//     }
-// }
-//
-// #[derive(Copy, Clone, Debug)]
-// pub enum ReagentMode {
-//     Contents(ModeRepr),
-//     Required(ModeRepr),
-//     Recipe(ModeRepr),
-// }
-//
-// impl<'i, R: AstCommonRule, P: Parser<R>, E: AstError<R>> AstNode<'i, R, P, E> for ReagentMode {
-//     type Output = Self;
-//
-//     const RULE: R = R::RULE_REAGENT_MODE;
-//
-//     fn try_from_pair(pair: Pair<'i, R>) -> Result<Self::Output, E> {
-//         let s = pair.as_str();
-//         #[rustfmt::skip]
-//         match s.to_lowercase().as_str() {
-//             "0" | "contents" | "cns" => Ok(Self::Contents(get_repr("0", s))),
-//             "1" | "required" | "rqd" => Ok(Self::Required(get_repr("1", s))),
-//             "2" | "recipe"   | "rcp" => Ok(Self::Recipe(get_repr("2", s))),
-//             _ => Err(E::pair_wrong_rule("a reagent mode", pair)),
-//         }
-//     }
-// }
-//
-// impl Display for ReagentMode {
-//     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//         match self {
-//             Self::Contents(mode_repr) => write!(f, "{}", repr_disp(mode_repr, "0", "Contents")),
-//             Self::Required(mode_repr) => write!(f, "{}", repr_disp(mode_repr, "1", "Required")),
-//             Self::Recipe(mode_repr)   => write!(f, "{}", repr_disp(mode_repr, "2", "Recipe")),
-//         }
-//     }
-// }
+/// Write a number the way both languages print literals.
+///
+/// Integers are written without a fractional part, negative zero as `0`, and non-finite values
+/// as the IC10 constants `nan`, `pinf` and `ninf`. With the alternate flag (`{:#}`) the number is
+/// written in its shortest round-trippable form (see [`shortest_num`]).
+pub fn fmt_num(f: &mut fmt::Formatter, n: f64) -> fmt::Result {
+    if n.is_nan() {
+        write!(f, "nan")
+    } else if n.is_infinite() {
+        write!(f, "{}", if n > 0.0 { "pinf" } else { "ninf" })
+    } else if n == 0.0 {
+        write!(f, "0")
+    } else if f.alternate() {
+        write!(f, "{}", shortest_num(n))
+    } else {
+        write!(f, "{}", n)
+    }
+}
+
+/// The shortest text which parses back to exactly the same (finite) number.
+///
+/// Both the plain and exponent forms are shortest round-trip representations of their digits;
+/// the exponent form wins only when it is strictly shorter (e.g. `1e-5` over `0.00001`).
+pub fn shortest_num(n: f64) -> String {
+    let plain = format!("{}", n);
+    let exp = format!("{:e}", n);
+    if exp.len() < plain.len() {
+        exp
+    } else {
+        plain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A number displayed with `fmt_num`
+    struct Num(f64);
+
+    impl fmt::Display for Num {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            fmt_num(f, self.0)
+        }
+    }
+
+    #[test]
+    fn fmt_num_literals() {
+        assert_eq!(Num(3.0).to_string(), "3");
+        assert_eq!(Num(-2.5).to_string(), "-2.5");
+        assert_eq!(Num(-0.0).to_string(), "0");
+        assert_eq!(Num(f64::NAN).to_string(), "nan");
+        assert_eq!(Num(f64::INFINITY).to_string(), "pinf");
+        assert_eq!(Num(f64::NEG_INFINITY).to_string(), "ninf");
+        assert_eq!(Num(0.00001).to_string(), "0.00001");
+    }
+
+    #[test]
+    fn shortest_num_round_trips() {
+        assert_eq!(shortest_num(0.00001), "1e-5");
+        assert_eq!(shortest_num(1500.0), "1500");
+        assert_eq!(shortest_num(1500000.0), "1.5e6");
+        assert_eq!(shortest_num(1e21), "1e21");
+        assert_eq!(shortest_num(-0.25), "-0.25");
+        assert_eq!(shortest_num(0.1 + 0.2), "0.30000000000000004");
+        let nums = [0.00001, 1e21, 0.1 + 0.2, 123.456e-10, f64::MAX, f64::MIN];
+        for n in nums {
+            assert_eq!(shortest_num(n).parse::<f64>(), Ok(n));
+        }
+    }
+
+    #[test]
+    fn fmt_num_alternate() {
+        assert_eq!(format!("{:#}", Num(0.00001)), "1e-5");
+        assert_eq!(format!("{:#}", Num(12.0)), "12");
+        assert_eq!(format!("{:#}", Num(-0.0)), "0");
+        assert_eq!(format!("{:#}", Num(f64::NAN)), "nan");
+    }
+}
@@ -0,0 +1,53 @@
+use std::{fmt, fmt::Display};
+
+use crate::constants::{logic_type, slot_logic_type, ConstError, LOGIC_TYPES, SLOT_LOGIC_TYPES};
+
+/// A device logic type (e.g. `Setting`), stored by its canonical name.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LogicType(&'static str);
+
+impl LogicType {
+    /// Look up a logic type by (case-insensitive) name.
+    pub fn new(name: &str) -> Result<Self, ConstError> {
+        logic_type(name).map(Self)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+
+    pub fn value(&self) -> i64 {
+        LOGIC_TYPES.value(self.0).unwrap()
+    }
+}
+
+impl Display for LogicType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A device slot logic type (e.g. `Occupied`), stored by its canonical name.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SlotLogicType(&'static str);
+
+impl SlotLogicType {
+    /// Look up a slot logic type by (case-insensitive) name.
+    pub fn new(name: &str) -> Result<Self, ConstError> {
+        slot_logic_type(name).map(Self)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+
+    pub fn value(&self) -> i64 {
+        SLOT_LOGIC_TYPES.value(self.0).unwrap()
+    }
+}
+
+impl Display for SlotLogicType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
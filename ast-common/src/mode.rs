@@ -0,0 +1,94 @@
+use std::{fmt, fmt::Display};
+
+use crate::constants::{ConstError, ConstTable, BATCH_MODES, REAGENT_MODES};
+
+/// How a mode was written in the source: as an integer or by name.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ModeRepr {
+    Int,
+    Str,
+}
+
+// Parse a mode token, either an integer or a (case-insensitive) name from the table
+fn parse_mode(table: &ConstTable, s: &str) -> Result<(i64, ModeRepr), ConstError> {
+    match s.parse::<i64>() {
+        Ok(value) if table.name_of(value).is_some() => Ok((value, ModeRepr::Int)),
+        Ok(_) => Err(ConstError::unknown(table.kind(), s, None)),
+        Err(_) => table
+            .try_canonical(s)
+            .map(|name| (table.value(name).unwrap(), ModeRepr::Str)),
+    }
+}
+
+macro_rules! def_mode {
+    ($name:ident, $table:ident, [$(($variant:ident, $value:literal)),*$(,)*]) => {
+        #[derive(Copy, Clone, Debug)]
+        pub enum $name {
+            $(
+                $variant(ModeRepr),
+            )*
+        }
+
+        impl $name {
+            pub fn value(&self) -> i64 {
+                match self {
+                    $(
+                        Self::$variant(..) => $value,
+                    )*
+                }
+            }
+
+            pub fn repr(&self) -> ModeRepr {
+                match self {
+                    $(
+                        Self::$variant(repr) => *repr,
+                    )*
+                }
+            }
+
+            pub fn from_value(value: i64, repr: ModeRepr) -> Option<Self> {
+                match value {
+                    $(
+                        $value => Some(Self::$variant(repr)),
+                    )*
+                    _ => None,
+                }
+            }
+
+            /// Parse an integer or (case-insensitive) name.
+            pub fn from_token(s: &str) -> Result<Self, ConstError> {
+                let (value, repr) = parse_mode(&$table, s)?;
+                Ok(Self::from_value(value, repr).unwrap())
+            }
+
+            /// The same mode, written as an integer.
+            pub fn to_int(self) -> Self {
+                Self::from_value(self.value(), ModeRepr::Int).unwrap()
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self.repr() {
+                    ModeRepr::Int => write!(f, "{}", self.value()),
+                    ModeRepr::Str => write!(f, "{}", $table.name_of(self.value()).unwrap()),
+                }
+            }
+        }
+    };
+}
+
+#[rustfmt::skip]
+def_mode!(BatchMode, BATCH_MODES, [
+    (Avg, 0),
+    (Sum, 1),
+    (Min, 2),
+    (Max, 3),
+]);
+
+#[rustfmt::skip]
+def_mode!(ReagentMode, REAGENT_MODES, [
+    (Contents, 0),
+    (Required, 1),
+    (Recipe,   2),
+]);
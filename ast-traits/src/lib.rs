@@ -9,6 +9,9 @@ use pest::error::Error as PegError;
 use pest::iterators::{Pair, Pairs};
 use pest::{Parser, RuleType};
 
+mod span;
+pub use span::{peg_message, SourceSpan};
+
 pub trait AstRule: RuleType {
     fn eoi() -> Self;
 }
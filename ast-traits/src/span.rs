@@ -0,0 +1,172 @@
+use std::{fmt, fmt::Display};
+
+use pest::error::{Error as PegError, ErrorVariant, LineColLocation};
+use pest::{RuleType, Span};
+
+/// A location in a source, for error reporting.
+///
+/// Lines and columns are 1-based, and columns and lengths count characters. Displaying a span
+/// shows its location followed by the source line (if known) with a caret under the span.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub file: Option<String>,
+    pub line: usize,
+    pub col: usize,
+    pub len: usize,
+    /// The text of the source line
+    pub text: Option<String>,
+}
+
+impl SourceSpan {
+    /// A span on a line whose text isn't known.
+    pub fn new(line: usize, col: usize, len: usize) -> Self {
+        Self {
+            file: None,
+            line,
+            col,
+            len,
+            text: None,
+        }
+    }
+
+    /// A span of the whole line (excluding indentation and trailing whitespace).
+    pub fn line(line: usize, text: &str) -> Self {
+        let indent = text.chars().take_while(|c| c.is_whitespace()).count();
+        let len = text.trim().chars().count();
+        Self::new(line, indent + 1, len).with_text(text)
+    }
+
+    /// A span of the first argument of a line equal to `token`, or the whole line if there's none.
+    ///
+    /// The first word of the line is skipped, since it's an instruction or tag rather than an
+    /// argument.
+    pub fn token(line: usize, text: &str, token: &str) -> Self {
+        let mut col = 1;
+        for (i, word) in text.split(' ').enumerate() {
+            if i > 0 && word == token {
+                return Self::new(line, col, word.chars().count()).with_text(text);
+            }
+            col += word.chars().count() + 1;
+        }
+        Self::line(line, text)
+    }
+
+    /// The span of a pest span, on the line that pest reports.
+    pub fn from_pest(span: Span) -> Self {
+        let (line, col) = span.start_pos().line_col();
+        let text = span.start_pos().line_of();
+        // A span may continue onto the next lines, but only its first line is shown
+        let len = span.as_str().lines().next().unwrap_or("").chars().count();
+        Self::new(line, col, len).with_text(text)
+    }
+
+    /// The span of a pest error in the source that was parsed, on the line that pest reports.
+    pub fn from_peg_error<R: RuleType>(e: &PegError<R>, source: &str) -> Self {
+        let (line, col, len) = match e.line_col {
+            LineColLocation::Pos((line, col)) => (line, col, 1),
+            LineColLocation::Span((line, col), (end_line, end_col)) => {
+                let len = if end_line == line { end_col - col } else { 1 };
+                (line, col, len)
+            }
+        };
+        let text = source.lines().nth(line - 1).unwrap_or("");
+        Self::new(line, col, len).with_text(text)
+    }
+
+    pub fn with_file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_owned());
+        self
+    }
+
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = Some(text.trim_end_matches(&['\r', '\n'][..]).to_owned());
+        self
+    }
+}
+
+impl Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Self {
+            file,
+            line,
+            col,
+            len,
+            text,
+        } = self;
+
+        let gutter = " ".repeat(line.to_string().len());
+        match file {
+            Some(file) => write!(f, "{}--> {}:{}:{}", gutter, file, line, col)?,
+            None => write!(f, "{}--> line {}:{}", gutter, line, col)?,
+        }
+        if let Some(text) = text {
+            // Keep tabs so that the caret lines up with the text
+            let pad = text
+                .chars()
+                .take(col.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            write!(f, "\n{} |", gutter)?;
+            write!(f, "\n{} | {}", line, text)?;
+            write!(f, "\n{} | {}{}", gutter, pad, "^".repeat((*len).max(1)))?;
+        }
+        Ok(())
+    }
+}
+
+/// A short description of a pest error, without its location.
+pub fn peg_message<R: RuleType>(e: &PegError<R>) -> String {
+    fn rules<R: RuleType>(rules: &[R]) -> String {
+        let names = rules.iter().map(|rule| format!("{:?}", rule));
+        names.collect::<Vec<_>>().join(", ")
+    }
+
+    match &e.variant {
+        ErrorVariant::ParsingError {
+            positives,
+            negatives,
+        } => match (positives.is_empty(), negatives.is_empty()) {
+            (false, false) => format!(
+                "unexpected {}, expected {}",
+                rules(negatives),
+                rules(positives)
+            ),
+            (false, true) => format!("expected {}", rules(positives)),
+            (true, false) => format!("unexpected {}", rules(negatives)),
+            (true, true) => "unknown parsing error".to_owned(),
+        },
+        ErrorVariant::CustomError { message } => message.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_location() {
+        assert_eq!(SourceSpan::new(3, 5, 2).to_string(), " --> line 3:5");
+        let span = SourceSpan::new(12, 1, 1).with_file("main.myps");
+        assert_eq!(span.to_string(), "  --> main.myps:12:1");
+    }
+
+    #[test]
+    fn display_caret() {
+        let span = SourceSpan::token(2, "add r0 r1 x", "x").with_file("a.mips");
+        let expected = " --> a.mips:2:11\n  |\n2 | add r0 r1 x\n  |           ^";
+        assert_eq!(span.to_string(), expected);
+
+        let span = SourceSpan::line(10, "\tx = 1\r\n");
+        let expected = "  --> line 10:2\n   |\n10 | \tx = 1\n   | \t^^^^^";
+        assert_eq!(span.to_string(), expected);
+    }
+
+    #[test]
+    fn token_falls_back_to_line() {
+        let span = SourceSpan::token(1, "  s d0 On r0", "d1");
+        assert_eq!((span.col, span.len), (3, 10));
+        // The instruction isn't an argument
+        let span = SourceSpan::token(1, "s s s", "s");
+        assert_eq!((span.col, span.len), (3, 1));
+    }
+}
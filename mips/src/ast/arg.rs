@@ -1,6 +1,9 @@
 use std::{fmt, fmt::Display};
 
-use crate::ast::{Dev, DevBase, LineAbs, LineRel, MipsNode, Num, Reg, RegBase, RegLit};
+use crate::ast::{
+    BatchMode, Dev, DevBase, LineAbs, LineRel, LogicType, MipsNode, Num, ReagentMode, Reg, RegBase,
+    RegLit, SlotLogicType,
+};
 use crate::{Alias, Aliases, MipsError, MipsParser, MipsResult, Pair, Rule};
 use ast_traits::{AstNode, IntoAst};
 
@@ -11,6 +14,10 @@ pub enum Arg {
     Num(Num),
     LineAbs(LineAbs),
     LineRel(LineRel),
+    BatchMode(BatchMode),
+    ReagentMode(ReagentMode),
+    LogicType(LogicType),
+    SlotLogicType(SlotLogicType),
     String(String),
 }
 
@@ -41,17 +48,6 @@ impl Arg {
             _ => None,
         }
     }
-
-    pub fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
-        match self {
-            Self::Dev(dev) => Ok(Self::Dev(dev.reduce(aliases)?)),
-            Self::Reg(reg) => Ok(Self::Reg(reg.reduce(aliases)?)),
-            Self::Num(num) => Ok(Self::Num(num.reduce(aliases)?)),
-            Self::LineAbs(line_abs) => Ok(Self::LineAbs(line_abs.reduce(aliases)?)),
-            Self::LineRel(line_rel) => Ok(Self::LineRel(line_rel.reduce(aliases)?)),
-            Self::String(..) => Ok(self),
-        }
-    }
 }
 
 impl<'i> MipsNode<'i> for Arg {
@@ -62,7 +58,11 @@ impl<'i> MipsNode<'i> for Arg {
             Self::Num(num) => num.as_reg_base(),
             Self::LineAbs(line_abs) => line_abs.as_reg_base(),
             Self::LineRel(line_rel) => line_rel.as_reg_base(),
-            Self::String(..) => None,
+            Self::BatchMode(..)
+            | Self::ReagentMode(..)
+            | Self::LogicType(..)
+            | Self::SlotLogicType(..)
+            | Self::String(..) => None,
         }
     }
 
@@ -73,7 +73,11 @@ impl<'i> MipsNode<'i> for Arg {
             Self::Num(num) => num.as_reg_base_mut(),
             Self::LineAbs(line_abs) => line_abs.as_reg_base_mut(),
             Self::LineRel(line_rel) => line_rel.as_reg_base_mut(),
-            Self::String(..) => None,
+            Self::BatchMode(..)
+            | Self::ReagentMode(..)
+            | Self::LogicType(..)
+            | Self::SlotLogicType(..)
+            | Self::String(..) => None,
         }
     }
 
@@ -84,7 +88,11 @@ impl<'i> MipsNode<'i> for Arg {
             Self::Num(num) => num.as_alias(),
             Self::LineAbs(line_abs) => line_abs.as_alias(),
             Self::LineRel(line_rel) => line_rel.as_alias(),
-            Self::String(..) => None,
+            Self::BatchMode(..)
+            | Self::ReagentMode(..)
+            | Self::LogicType(..)
+            | Self::SlotLogicType(..)
+            | Self::String(..) => None,
         }
     }
 
@@ -96,7 +104,24 @@ impl<'i> MipsNode<'i> for Arg {
             Self::Num(num)          => num.set_fixed(fixed),
             Self::LineAbs(line_abs) => line_abs.set_fixed(fixed),
             Self::LineRel(line_rel) => line_rel.set_fixed(fixed),
-            Self::String(_) => {},
+            Self::BatchMode(_)
+            | Self::ReagentMode(_)
+            | Self::LogicType(_)
+            | Self::SlotLogicType(_)
+            | Self::String(_) => {},
+        }
+    }
+
+    fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
+        match self {
+            Self::Dev(dev) => Ok(Self::Dev(dev.reduce(aliases)?)),
+            Self::Reg(reg) => Ok(Self::Reg(reg.reduce(aliases)?)),
+            Self::Num(num) => Ok(Self::Num(num.reduce(aliases)?)),
+            Self::LineAbs(line_abs) => Ok(Self::LineAbs(line_abs.reduce(aliases)?)),
+            Self::LineRel(line_rel) => Ok(Self::LineRel(line_rel.reduce(aliases)?)),
+            Self::BatchMode(mode) => Ok(Self::BatchMode(mode.reduce(aliases)?)),
+            Self::ReagentMode(mode) => Ok(Self::ReagentMode(mode.reduce(aliases)?)),
+            Self::LogicType(..) | Self::SlotLogicType(..) | Self::String(..) => Ok(self),
         }
     }
 }
@@ -134,7 +159,7 @@ impl<'i> AstNode<'i, Rule, MipsParser, MipsError> for Arg {
             Rule::reg => Ok(Self::Reg(pair.try_into_ast()?)),
             Rule::num => Ok(Self::Num(pair.try_into_ast()?)),
             Rule::alias => Ok(Self::String(pair.as_str().into())),
-            _ => panic!("{:?}", pair),
+            _ => Err(MipsError::pair_wrong_rule("an argument", pair)),
         }
     }
 }
@@ -157,18 +182,27 @@ impl_into_arg!(
     (Num, Arg::Num),
     (LineAbs, Arg::LineAbs),
     (LineRel, Arg::LineRel),
+    (BatchMode, Arg::BatchMode),
+    (ReagentMode, Arg::ReagentMode),
+    (LogicType, Arg::LogicType),
+    (SlotLogicType, Arg::SlotLogicType),
     (String, Arg::String),
 );
 
 impl Display for Arg {
+    // Forwards the formatter so that flags (e.g. `{:#}` for shortest numbers) reach the value
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::Dev(t) => write!(f, "{}", t),
-            Self::Reg(t) => write!(f, "{}", t),
-            Self::Num(t) => write!(f, "{}", t),
-            Self::LineAbs(t) => write!(f, "{}", t),
-            Self::LineRel(t) => write!(f, "{}", t),
-            Self::String(t) => write!(f, "{}", t),
+            Self::Dev(t) => t.fmt(f),
+            Self::Reg(t) => t.fmt(f),
+            Self::Num(t) => t.fmt(f),
+            Self::LineAbs(t) => t.fmt(f),
+            Self::LineRel(t) => t.fmt(f),
+            Self::BatchMode(t) => t.fmt(f),
+            Self::ReagentMode(t) => t.fmt(f),
+            Self::LogicType(t) => t.fmt(f),
+            Self::SlotLogicType(t) => t.fmt(f),
+            Self::String(t) => t.fmt(f),
         }
     }
 }
@@ -188,3 +222,68 @@ impl<'i> AstNode<'i, Rule, MipsParser, MipsError> for DevOrReg {
         }
     }
 }
+
+/// A logic type token (e.g. `Setting`), matched case-insensitively and stored canonically.
+pub struct LogicTypeArg;
+
+impl<'i> AstNode<'i, Rule, MipsParser, MipsError> for LogicTypeArg {
+    type Output = Arg;
+
+    const RULE: Rule = Rule::arg;
+
+    fn try_from_pair(pair: Pair) -> MipsResult<Self::Output> {
+        match pair.as_rule() {
+            Rule::alias => Ok(Arg::LogicType(LogicType::new(pair.as_str())?)),
+            _ => Err(MipsError::pair_wrong_rule("a logic type", pair)),
+        }
+    }
+}
+
+/// A slot logic type token (e.g. `Occupied`), matched case-insensitively and stored canonically.
+pub struct SlotLogicTypeArg;
+
+impl<'i> AstNode<'i, Rule, MipsParser, MipsError> for SlotLogicTypeArg {
+    type Output = Arg;
+
+    const RULE: Rule = Rule::arg;
+
+    fn try_from_pair(pair: Pair) -> MipsResult<Self::Output> {
+        match pair.as_rule() {
+            Rule::alias => Ok(Arg::SlotLogicType(SlotLogicType::new(pair.as_str())?)),
+            _ => Err(MipsError::pair_wrong_rule("a slot logic type", pair)),
+        }
+    }
+}
+
+macro_rules! impl_mode_arg {
+    ($(($name:ident, $mode:ident, $expected:literal)),*$(,)*) => {
+        $(
+            impl<'i> AstNode<'i, Rule, MipsParser, MipsError> for $name {
+                type Output = Arg;
+
+                const RULE: Rule = Rule::arg;
+
+                fn try_from_pair(pair: Pair) -> MipsResult<Self::Output> {
+                    if let Ok(mode) = $mode::try_from_pair(pair.clone()) {
+                        return Ok(Arg::$mode(mode));
+                    }
+                    match pair.as_rule() {
+                        Rule::reg | Rule::alias => Ok(Arg::Num(pair.try_into_ast()?)),
+                        _ => Err(MipsError::pair_wrong_rule($expected, pair)),
+                    }
+                }
+            }
+        )*
+    }
+}
+
+/// A batch mode (e.g. `Maximum` or `3`), or a register or alias holding one.
+pub struct BatchModeArg;
+
+/// A reagent mode (e.g. `Contents` or `0`), or a register or alias holding one.
+pub struct ReagentModeArg;
+
+impl_mode_arg!(
+    (BatchModeArg, BatchMode, "a batch mode"),
+    (ReagentModeArg, ReagentMode, "a reagent mode"),
+);
@@ -136,15 +136,6 @@ pub enum Dev {
     Alias(String),
 }
 
-impl Dev {
-    pub fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
-        match self {
-            Self::Base(..) => Ok(self),
-            Self::Alias(key) => Ok(Self::Base(aliases.try_get_dev_base(&key)?)),
-        }
-    }
-}
-
 impl From<DevBase> for Dev {
     fn from(dev_base: DevBase) -> Self {
         Self::Base(dev_base)
@@ -184,6 +175,13 @@ impl<'i> MipsNode<'i> for Dev {
     }
 
     fn set_fixed(&mut self, _fixed: bool) {}
+
+    fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
+        match self {
+            Self::Base(..) => Ok(self),
+            Self::Alias(key) => Ok(Self::Base(aliases.try_get_dev_base(&key)?)),
+        }
+    }
 }
 
 impl<'i> AstNode<'i, Rule, MipsParser, MipsError> for Dev {
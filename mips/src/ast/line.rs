@@ -9,11 +9,17 @@ use crate::{MipsError, MipsParser, MipsResult, Pair, Rule};
 pub struct Line {
     pub stmt: Stmt,
     pub comment_opt: Option<String>,
+    /// The (1-based) line of the source that this line was parsed or translated from
+    pub source_line: Option<usize>,
 }
 
 impl Line {
     pub fn new(stmt: Stmt, comment_opt: Option<String>) -> Self {
-        Self { stmt, comment_opt }
+        Self {
+            stmt,
+            comment_opt,
+            source_line: None,
+        }
     }
 
     pub fn new_no_comment(stmt: Stmt) -> Self {
@@ -28,26 +34,29 @@ impl<'i> AstNode<'i, Rule, MipsParser, MipsError> for Line {
 
     fn try_from_pair(pair: Pair) -> MipsResult<Self> {
         let mut pairs = pair.into_inner();
-        let stmt = pairs.next_pair().unwrap().try_into_ast().unwrap();
+        let stmt = pairs.next_pair()?.try_into_ast()?;
         let comment_opt = pairs.next().and_then(|pair| {
             matches!(pair.as_rule(), Rule::comment).then_some(pair.as_str().to_owned())
         });
-        Ok(Self { stmt, comment_opt })
+        Ok(Self::new(stmt, comment_opt))
     }
 }
 
 impl Display for Line {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let Self { stmt, comment_opt } = self;
+        let Self {
+            stmt, comment_opt, ..
+        } = self;
 
         if let Some(comment) = comment_opt {
             if matches!(stmt, Stmt::Empty(..)) {
                 write!(f, "{}", comment)
             } else {
-                write!(f, "{} {}", stmt, comment)
+                stmt.fmt(f)?;
+                write!(f, " {}", comment)
             }
         } else {
-            write!(f, "{}", stmt)
+            stmt.fmt(f)
         }
     }
 }
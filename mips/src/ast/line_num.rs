@@ -7,12 +7,6 @@ use ast_traits::{AstNode, IntoAst};
 #[derive(Clone, Debug)]
 pub struct LineAbs(pub Num);
 
-impl LineAbs {
-    pub fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
-        Ok(Self(self.0.reduce(aliases)?))
-    }
-}
-
 impl<'i> MipsNode<'i> for LineAbs {
     fn as_reg_base(&self) -> Option<RegBase> {
         self.0.as_reg_base()
@@ -30,6 +24,10 @@ impl<'i> MipsNode<'i> for LineAbs {
     fn set_fixed(&mut self, fixed: bool) {
         self.0.set_fixed(fixed);
     }
+
+    fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
+        Ok(Self(self.0.reduce(aliases)?))
+    }
 }
 
 impl From<f64> for LineAbs {
@@ -64,12 +62,6 @@ impl Display for LineAbs {
 #[derive(Clone, Debug)]
 pub struct LineRel(pub Num);
 
-impl LineRel {
-    pub fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
-        Ok(Self(self.0.reduce(aliases)?))
-    }
-}
-
 impl<'i> MipsNode<'i> for LineRel {
     fn as_reg_base(&self) -> Option<RegBase> {
         self.0.as_reg_base()
@@ -86,6 +78,10 @@ impl<'i> MipsNode<'i> for LineRel {
     fn set_fixed(&mut self, fixed: bool) {
         self.0.set_fixed(fixed);
     }
+
+    fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
+        Ok(Self(self.0.reduce(aliases)?))
+    }
 }
 
 impl From<f64> for LineRel {
@@ -1,11 +1,6 @@
-use crate::{Mips, MipsError, MipsParser, MipsResult, Rule};
+use crate::{Aliases, Mips, MipsError, MipsParser, MipsResult, Rule};
 pub use ast_traits::AstNode;
 
-// pub use ast_common::ModeRepr;
-// pub type BatchMode = ast_common::BatchMode;
-// pub type ReagentMode = ast_common::ReagentMode;
-// pub type ReagentMode<'a> = ast_common::ReagentMode<'a, Rule, MipsParser, MipsError>;
-
 #[derive(Copy, Clone, Hash, Debug)]
 pub enum FixMode {
     None,
@@ -41,7 +36,13 @@ pub trait MipsNode<'i>: AstNode<'i, Rule, MipsParser, MipsError> + std::fmt::Deb
 
     fn set_fixed(&mut self, fixed: bool);
 
-    // fn reduce(self, mips: &Mips) -> MipsResult<Self> where Self: Sized;
+    /// Reduce to canonical form (aliases to what they alias, named modes to integers).
+    fn reduce(self, _aliases: &Aliases) -> MipsResult<Self>
+    where
+        Self: Sized,
+    {
+        Ok(self)
+    }
 
     fn get_reg_base(&self, mips: &Mips) -> MipsResult<Option<RegBase>> {
         if let Some(reg_base) = self.as_reg_base() {
@@ -63,34 +64,6 @@ pub trait MipsNode<'i>: AstNode<'i, Rule, MipsParser, MipsError> + std::fmt::Deb
     }
 }
 
-// impl<'i> MipsNode<'i> for BatchMode {
-//     fn as_reg_base(&self) -> Option<RegBase> { None }
-//     fn as_reg_base_mut(&mut self) -> Option<&mut RegBase> { None }
-//     fn as_alias(&self) -> Option<&String> { None }
-//     fn reduce(self, _mips: &Mips) -> MipsResult<Self> {
-//         match self {
-//             Self::Avg(..) => Ok(Self::Avg(ModeRepr::Int)),
-//             Self::Sum(..) => Ok(Self::Sum(ModeRepr::Int)),
-//             Self::Min(..) => Ok(Self::Min(ModeRepr::Int)),
-//             Self::Max(..) => Ok(Self::Max(ModeRepr::Int)),
-//         }
-//     }
-// }
-
-// impl<'i> MipsNode<'i> for ReagentMode {
-//     fn as_reg_base(&self) -> Option<RegBase> { None }
-//     fn as_reg_base_mut(&mut self) -> Option<&mut RegBase> { None }
-//     fn as_alias(&self) -> Option<&String> { None }
-//     fn reduce(self, _mips: &Mips) -> MipsResult<Self> {
-//         #[rustfmt::skip]
-//         match self {
-//             Self::Contents(..) => Ok(Self::Contents(ModeRepr::Int)),
-//             Self::Required(..) => Ok(Self::Required(ModeRepr::Int)),
-//             Self::Recipe(..)   => Ok(Self::Recipe(ModeRepr::Int)),
-//         }
-//     }
-// }
-
 mod dev;
 pub use dev::{Dev, DevBase, DevLit};
 
@@ -107,10 +80,11 @@ mod line_num;
 pub use line_num::{LineAbs, LineRel};
 
 mod arg;
-pub use arg::{Arg, DevOrReg};
+pub use arg::{Arg, BatchModeArg, DevOrReg, LogicTypeArg, ReagentModeArg, SlotLogicTypeArg};
+
+pub use ast_common::{BatchMode, LogicType, ModeRepr, ReagentMode, SlotLogicType};
 
-// mod mode;
-// pub use mode::{BatchMode, ReagentMode};
+mod mode;
 
 mod stmt;
 pub use stmt::Stmt;
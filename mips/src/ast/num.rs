@@ -1,5 +1,7 @@
 use std::{fmt, fmt::Display};
 
+use ast_common::fmt_num;
+
 use crate::ast::{MipsNode, Reg, RegBase};
 use crate::{Alias, Aliases, MipsError, MipsParser, MipsResult, Pair, Rule};
 use ast_traits::{AstNode, IntoAst};
@@ -11,24 +13,6 @@ pub enum Num {
     Alias(String),
 }
 
-impl Num {
-    pub fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
-        match self {
-            Self::Lit(..) | Self::Reg(..) => Ok(self),
-            Self::Alias(key) => {
-                let alias = aliases.get(&key).ok_or(MipsError::alias_undefined(&key))?;
-                match alias {
-                    Alias::Num(n) => Ok(Self::Lit(*n)),
-                    Alias::Reg(reg_base) => Ok(Self::Reg(reg_base.clone())),
-                    Alias::Dev(..) => {
-                        Err(MipsError::alias_wrong_kind("a number or register", alias))
-                    }
-                }
-            }
-        }
-    }
-}
-
 impl<'i> MipsNode<'i> for Num {
     fn as_reg_base(&self) -> Option<RegBase> {
         match self {
@@ -58,6 +42,22 @@ impl<'i> MipsNode<'i> for Num {
             Self::Alias(_) => {},
         }
     }
+
+    fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
+        match self {
+            Self::Lit(..) | Self::Reg(..) => Ok(self),
+            Self::Alias(key) => {
+                let alias = aliases.get(&key).ok_or(MipsError::alias_undefined(&key))?;
+                match alias {
+                    Alias::Num(n) => Ok(Self::Lit(*n)),
+                    Alias::Reg(reg_base) => Ok(Self::Reg(reg_base.clone())),
+                    Alias::Dev(..) => {
+                        Err(MipsError::alias_wrong_kind("a number or register", alias))
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl<'i> AstNode<'i, Rule, MipsParser, MipsError> for Num {
@@ -67,7 +67,7 @@ impl<'i> AstNode<'i, Rule, MipsParser, MipsError> for Num {
 
     fn try_from_pair(pair: Pair) -> MipsResult<Self> {
         match pair.as_rule() {
-            Rule::num => Ok(Self::Lit(pair.as_str().parse().unwrap())),
+            Rule::num => Ok(Self::Lit(pair.as_str().parse()?)),
             Rule::reg => Ok(Num::Reg(pair.try_into_ast()?)),
             Rule::alias => Ok(Num::Alias(pair.as_str().into())),
             _ => Err(MipsError::pair_wrong_rule("a number", pair)),
@@ -105,7 +105,7 @@ impl From<String> for Num {
 impl Display for Num {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::Lit(t) => write!(f, "{}", t),
+            Self::Lit(t) => fmt_num(f, *t),
             Self::Reg(t) => write!(f, "{}", t),
             Self::Alias(t) => write!(f, "{}", t),
         }
@@ -200,13 +200,6 @@ impl Reg {
             _ => None,
         }
     }
-
-    pub fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
-        match self {
-            Self::Alias { key, .. } => Ok(Self::Base(aliases.try_get_reg_base(&key)?)),
-            _ => Ok(self),
-        }
-    }
 }
 
 impl<'i> MipsNode<'i> for Reg {
@@ -237,6 +230,13 @@ impl<'i> MipsNode<'i> for Reg {
             Self::Alias { fixed: f, .. } => *f = fixed,
         }
     }
+
+    fn reduce(self, aliases: &Aliases) -> MipsResult<Self> {
+        match self {
+            Self::Alias { key, .. } => Ok(Self::Base(aliases.try_get_reg_base(&key)?)),
+            _ => Ok(self),
+        }
+    }
 }
 
 impl From<RegBase> for Reg {
@@ -2,7 +2,7 @@ use std::{fmt, fmt::Display};
 
 use itertools::join;
 
-use ast_traits::{AstNode, AstPairs};
+use ast_traits::{AstErrorBase, AstNode, AstPairs, SourceSpan};
 
 use crate::ast::{Arg, MipsNode};
 use crate::{Aliases, MipsError, MipsParser, MipsResult, Pair, Rule};
@@ -36,18 +36,20 @@ macro_rules! def_stmt {
                     }
                     Rule::stmt => {
                         let mut pairs = pair.into_inner();
-                        let name_str = pairs.next_pair().unwrap().as_str();
+                        let name_pair = pairs.next_pair()?;
+                        let name_str = name_pair.as_str();
                         let arg_pairs = pairs.collect::<Vec<_>>();
                         #[allow(dead_code, unused_variables, unused_mut)]
                         match name_str {
                             $(
                                 $disp => {
                                     if $n_args != arg_pairs.len() {
+                                        let span = SourceSpan::from_pest(name_pair.as_span());
                                         return Err(MipsError::args_wrong_num(
                                             name_str,
                                             $n_args,
                                             arg_pairs.len()
-                                        ));
+                                        ).at(span));
                                     }
                                     let found = join(arg_pairs.iter().map(|pair| {
                                             format!("{:?}", pair.as_rule())
@@ -55,16 +57,21 @@ macro_rules! def_stmt {
                                     let mut pairs = arg_pairs.into_iter();
                                     let args: [Arg; $n_args] = [
                                         $({
-                                            let pair = pairs.next().unwrap();
+                                            let pair = pairs
+                                                .next()
+                                                .ok_or(AstErrorBase::PairsNotEnough)?;
+                                            let span = SourceSpan::from_pest(pair.as_span());
                                             let ast = <$arg_kind>::try_from_pair(pair)
-                                                .map_err(|_| {
-                                                    MipsError::args_wrong_kinds(
+                                                .map_err(|e| match e {
+                                                    // Keep the suggestion for misspelled names
+                                                    MipsError::ConstError(..) => e,
+                                                    _ => MipsError::args_wrong_kinds(
                                                         name_str,
                                                         $expected,
                                                         &found,
-                                                    )
+                                                    ),
                                                 })
-                                                .unwrap();
+                                                .map_err(|e| e.at(span))?;
                                             ast.into()
                                         }),*
                                     ];
@@ -72,7 +79,10 @@ macro_rules! def_stmt {
                                     Ok(stmt)
                                 }
                             )*
-                            _ => unreachable!(),
+                            _ => {
+                                let span = SourceSpan::from_pest(name_pair.as_span());
+                                Err(MipsError::instr_unknown(name_str).at(span))
+                            }
                         }
                     }
                     _ => Err(MipsError::pair_wrong_rule("a MIPS instruction", pair)),
@@ -81,6 +91,9 @@ macro_rules! def_stmt {
         }
 
         impl<'i> Stmt {
+            /// The names of the instructions.
+            pub const NAMES: &'static [&'static str] = &[$($disp),*];
+
             // $(
             //     #[allow(unused_variables, unused_mut)]
             //     pub fn $try(
@@ -154,12 +167,12 @@ macro_rules! def_stmt {
                 match self {
                     $(
                         Self::$name(args) => {
-                            let args_str = || join(args.iter(), " ");
-                            if args.is_empty() {
-                                write!(f, "{}", $disp)
-                            } else {
-                                write!(f, "{} {}", $disp, args_str())
+                            write!(f, "{}", $disp)?;
+                            for arg in args.iter() {
+                                write!(f, " ")?;
+                                arg.fmt(f)?;
                             }
+                            Ok(())
                         },
                     )*
                     Self::Tag(args) => {
@@ -180,6 +193,10 @@ use crate::ast::{
     Num as N,
     LineAbs as LA,
     LineRel as LR,
+    LogicTypeArg as LT,
+    SlotLogicTypeArg as SLT,
+    BatchModeArg as BM,
+    ReagentModeArg as RM,
 };
 use String as S;
 
@@ -192,13 +209,17 @@ def_stmt!(
     (Bdseal, "bdseal", 2, "dev,num",           [D, LA]),
     (Brdns,  "brdns",  2, "dev,num",           [D, LR]),
     (Brdse,  "brdse",  2, "dev,num",           [D, LR]),
-    (L,      "l",      3, "reg,dev,token",     [R, D, S]),
-    (Lb,     "lb",     4, "reg,num,token,num", [R, N, S, N]),
-    (Lr,     "lr",     4, "reg,dev,num,token", [R, D, N, S]),
-    (Ls,     "ls",     4, "reg,dev,num,token", [R, D, N, S]),
-    (S,      "s",      3, "dev,token,num",     [D, S, N]),
-    // (Sb,     "sb",     3, "num,dev,num",       [N, D, N]),
-    (Sb,     "sb",     3, "num,num,num",       [N, N, N]),
+    (L,      "l",      3, "reg,dev,logic",     [R, D, LT]),
+    (Lb,     "lb",     4, "reg,num,logic,mode", [R, N, LT, BM]),
+    (Lbn,    "lbn",    5, "reg,num,num,logic,mode", [R, N, N, LT, BM]),
+    (Lbs,    "lbs",    5, "reg,num,num,slot,mode", [R, N, N, SLT, BM]),
+    (Lr,     "lr",     4, "reg,dev,mode,token", [R, D, RM, S]),
+    (Ls,     "ls",     4, "reg,dev,num,slot",  [R, D, N, SLT]),
+    (S,      "s",      3, "dev,logic,num",     [D, LT, N]),
+    (Sb,     "sb",     3, "num,logic,num",     [N, LT, N]),
+    (Sbn,    "sbn",    4, "num,num,logic,num", [N, N, LT, N]),
+    (Sbs,    "sbs",    4, "num,num,slot,num",  [N, N, SLT, N]),
+    (Ss,     "ss",     4, "dev,num,slot,num",  [D, N, SLT, N]),
 
 
     // Flow Control, Branches and Jumps
@@ -303,7 +324,12 @@ def_stmt!(
     // Logic
     (And,    "and",    3, "reg,num,num",       [R, N, N]),
     (Nor,    "nor",    3, "reg,num,num",       [R, N, N]),
+    (Not,    "not",    2, "reg,num",           [R, N]),
     (Or,     "or",     3, "reg,num,num",       [R, N, N]),
+    (Sla,    "sla",    3, "reg,num,num",       [R, N, N]),
+    (Sll,    "sll",    3, "reg,num,num",       [R, N, N]),
+    (Sra,    "sra",    3, "reg,num,num",       [R, N, N]),
+    (Srl,    "srl",    3, "reg,num,num",       [R, N, N]),
     (Xor,    "xor",    3, "reg,num,num",       [R, N, N]),
 
     // Stack
@@ -0,0 +1,185 @@
+//! Command-line handling shared by the binaries: the failures they exit with, and the optimization
+//! options (`--config`, `--preset` and `--<pass>`/`--no-<pass>`).
+//!
+//! Nothing here exits the process; the binaries print a [`Failure`] and exit with its code.
+use std::fmt::{self, Display};
+
+use crate::OptimizationConfig;
+
+/// A failure of a binary: the errors to print, and whether they're errors of its usage.
+#[derive(Debug)]
+pub struct Failure {
+    pub errors: Vec<String>,
+    pub usage: bool,
+}
+
+impl Failure {
+    pub fn new(e: impl Display) -> Self {
+        Self::all(vec![e])
+    }
+
+    pub fn all<E: Display>(errors: Vec<E>) -> Self {
+        Self {
+            errors: errors.iter().map(E::to_string).collect(),
+            usage: false,
+        }
+    }
+
+    /// A failure of the command-line arguments.
+    pub fn usage(e: impl Display) -> Self {
+        Self {
+            usage: true,
+            ..Self::new(e)
+        }
+    }
+
+    /// The code to exit with: 2 for a usage failure, and 1 otherwise.
+    pub fn exit_code(&self) -> i32 {
+        if self.usage {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, e) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, "\n\n")?;
+            }
+            write!(f, "error: {}", e)?;
+        }
+        if self.usage {
+            write!(f, "\n\nFor more information try --help")?;
+        }
+        Ok(())
+    }
+}
+
+/// The usage of the optimization options, as lines of an options list.
+pub fn optimization_usage() -> String {
+    let fields = OptimizationConfig::FIELDS
+        .iter()
+        .map(|field| format!("{:32}{}\n", "", field.replace('_', "-")))
+        .collect::<String>();
+    let presets = OptimizationConfig::PRESETS
+        .iter()
+        .map(|preset| format!("{:32}{}\n", "", preset))
+        .collect::<String>();
+    format!(
+        "    --config <path>             read the optimization config from a file, as TOML if it has a
+                                .toml extension and as RON otherwise
+    --preset <name>             use a preset optimization config, one of:
+{}    --<pass>, --no-<pass>       enable or disable a part of the optimization, after any config
+                                or preset, where <pass> is one of:
+{}",
+        presets, fields
+    )
+}
+
+/// Parse command-line arguments, handling the optimization options and passing every other
+/// argument to `handle`, along with a getter of its value (for an option which takes one), and
+/// returning the first failure.
+///
+/// Options take their value either joined with `=` (e.g. `--preset=size`) or as the next argument.
+/// The config is the last one given by `--config` or `--preset` (or the default), with the
+/// `--<pass>` and `--no-<pass>` switches applied after it.
+pub fn parse_args(
+    mut args: impl Iterator<Item = String>,
+    mut handle: impl FnMut(&str, &mut dyn FnMut() -> Result<String, Failure>) -> Result<(), Failure>,
+) -> Result<OptimizationConfig, Failure> {
+    // The last config or preset given, to which switches are applied
+    let mut base = None;
+    let mut switches = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let (name, mut value_opt) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                (name.to_owned(), Some(value.to_owned()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            value_opt
+                .take()
+                .or_else(|| args.next())
+                .ok_or_else(|| Failure::usage(format!("{} expects a value", name)))
+        };
+        // A switch of a pass, if the option is one
+        let switch_opt = name.strip_prefix("--").and_then(|option| {
+            let (field, on) = match option.strip_prefix("no-") {
+                Some(field) => (field, false),
+                None => (option, true),
+            };
+            let field = field.replace('-', "_");
+            OptimizationConfig::FIELDS
+                .contains(&field.as_str())
+                .then_some((field, on))
+        });
+        match name.as_str() {
+            "--config" => {
+                let conf = OptimizationConfig::from_file(value()?);
+                base = Some(conf.map_err(Failure::new)?);
+            }
+            "--preset" => {
+                let conf = OptimizationConfig::preset(&value()?);
+                base = Some(conf.map_err(Failure::usage)?);
+            }
+            _ => match switch_opt {
+                Some(switch) => switches.push(switch),
+                None => handle(&name, &mut value)?,
+            },
+        }
+        if value_opt.is_some() {
+            return Err(Failure::usage(format!("{} doesn't take a value", name)));
+        }
+    }
+
+    let mut conf = base.unwrap_or_default();
+    for (field, on) in switches {
+        *conf.field_mut(&field).unwrap() = on;
+    }
+    Ok(conf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args<'a>(args: &'a [&str]) -> impl Iterator<Item = String> + 'a {
+        args.iter().map(|arg| arg.to_string())
+    }
+
+    #[test]
+    fn switches_after_preset() {
+        let mut handled = Vec::new();
+        let args = args(&["--remove-comments", "-o", "out", "--preset=debug", "in"]);
+        let conf = parse_args(args, |arg, value| {
+            let value_opt = if arg == "-o" { Some(value()?) } else { None };
+            handled.push((arg.to_owned(), value_opt));
+            Ok(())
+        })
+        .unwrap();
+        assert!(conf.remove_comments);
+        assert!(!conf.remove_tags && !conf.optimize_registers);
+        let expected = [
+            ("-o".to_owned(), Some("out".to_owned())),
+            ("in".to_owned(), None),
+        ];
+        assert_eq!(handled, expected);
+    }
+
+    #[test]
+    fn usage_failures() {
+        for bad in [&["--preset"][..], &["--preset=nope"], &["--remove-tags=1"]] {
+            let failure = parse_args(args(bad), |_, _| Ok(())).unwrap_err();
+            assert_eq!(failure.exit_code(), 2, "{:?}", bad);
+            let message = failure.to_string();
+            assert!(message.ends_with("try --help"), "{}", message);
+        }
+        let failure = parse_args(args(&["--config=/nonexistent.ron"]), |_, _| Ok(())).unwrap_err();
+        assert_eq!(failure.exit_code(), 1);
+    }
+}
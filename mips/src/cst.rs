@@ -0,0 +1,276 @@
+//! Lossless concrete syntax tree.
+//!
+//! Unlike [`Line`](crate::ast::Line), the CST keeps the trivia around each token (spacing, the
+//! spelling of numbers and names, blank lines), so that displaying a [`Cst`] reproduces its source
+//! exactly. It's the basis of the formatter ([`Cst::format`]).
+use std::{fmt, fmt::Display};
+
+use pest::Parser;
+
+use ast_traits::AstPairs;
+
+use crate::{MipsError, MipsParser, MipsResult, Rule};
+
+/// Comments further right than this column aren't aligned with the rest of their block.
+pub const COMMENT_COLUMN_MAX: usize = 48;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CstKind {
+    /// A tag, including its colon (e.g. `start:`)
+    Tag,
+    Instr,
+    Arg,
+    Comment,
+}
+
+#[derive(Clone, Debug)]
+pub struct CstToken {
+    pub kind: CstKind,
+    /// The trivia before the token
+    pub leading: String,
+    pub text: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct CstLine {
+    pub tokens: Vec<CstToken>,
+    /// The trivia after the last token (including a carriage return)
+    pub trailing: String,
+}
+
+impl CstLine {
+    pub fn parse(line: &str) -> MipsResult<Self> {
+        let (body, cr) = match line.strip_suffix('\r') {
+            Some(body) => (body, "\r"),
+            None => (line, ""),
+        };
+        let line_pair = MipsParser::parse(Rule::line, body)
+            .map_err(|e| MipsError::syntax(e, body))?
+            .only_pair()?;
+
+        let mut tokens = Vec::new();
+        let mut pos = 0;
+        let mut push = |kind, start: usize, end: usize| {
+            tokens.push(CstToken {
+                kind,
+                leading: body[pos..start].to_owned(),
+                text: body[start..end].to_owned(),
+            });
+            pos = end;
+        };
+        for pair in line_pair.into_inner() {
+            let span = pair.as_span();
+            match pair.as_rule() {
+                // The colon isn't part of the tag pair
+                Rule::tag => push(CstKind::Tag, span.start(), span.end() + 1),
+                Rule::stmt => {
+                    for pair in pair.into_inner() {
+                        let span = pair.as_span();
+                        let kind = match pair.as_rule() {
+                            Rule::instr => CstKind::Instr,
+                            _ => CstKind::Arg,
+                        };
+                        push(kind, span.start(), span.end());
+                    }
+                }
+                Rule::comment => push(CstKind::Comment, span.start(), span.end()),
+                _ => {}
+            }
+        }
+        let trailing = format!("{}{}", &body[pos..], cr);
+        Ok(Self { tokens, trailing })
+    }
+
+    fn tokens_of(&self, kind: CstKind) -> impl Iterator<Item = &CstToken> {
+        self.tokens.iter().filter(move |token| token.kind == kind)
+    }
+
+    pub fn tag(&self) -> Option<&CstToken> {
+        self.tokens_of(CstKind::Tag).next()
+    }
+
+    pub fn instr(&self) -> Option<&CstToken> {
+        self.tokens_of(CstKind::Instr).next()
+    }
+
+    pub fn args(&self) -> impl Iterator<Item = &CstToken> {
+        self.tokens_of(CstKind::Arg)
+    }
+
+    pub fn comment(&self) -> Option<&CstToken> {
+        self.tokens_of(CstKind::Comment).next()
+    }
+
+    /// Whether the line has no tag or instruction (it may still have a comment).
+    pub fn is_empty(&self) -> bool {
+        self.tag().is_none() && self.instr().is_none()
+    }
+
+    /// The tag or instruction of the line in normal form, or an empty string.
+    pub fn format_code(&self) -> String {
+        if let Some(tag) = self.tag() {
+            tag.text.clone()
+        } else if let Some(instr) = self.instr() {
+            let instr = instr.text.to_lowercase();
+            self.args()
+                .fold(instr, |code, arg| format!("{} {}", code, arg.text))
+        } else {
+            String::new()
+        }
+    }
+}
+
+impl Display for CstLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for CstToken { leading, text, .. } in self.tokens.iter() {
+            write!(f, "{}{}", leading, text)?;
+        }
+        write!(f, "{}", self.trailing)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Cst {
+    pub lines: Vec<CstLine>,
+}
+
+impl Cst {
+    /// Parse a whole source; the lines are split on `\n` so the final line may be empty.
+    pub fn parse(source: &str) -> MipsResult<Self> {
+        let lines = source
+            .split('\n')
+            .enumerate()
+            .map(|(i, line)| CstLine::parse(line).map_err(|e| e.on_line(i + 1, line)))
+            .collect::<MipsResult<Vec<_>>>()?;
+        Ok(Self { lines })
+    }
+
+    /// Format the source.
+    ///
+    /// Instructions are lowercased and their arguments separated by single spaces, trailing
+    /// comments are aligned in a column per block of lines (blocks being separated by blank or
+    /// comment-only lines), and blank lines are kept.
+    pub fn format(&self) -> String {
+        let mut lines = self.lines.as_slice();
+        // Keep a final newline, but no trailing blank lines
+        while let Some((last, init)) = lines.split_last() {
+            if last.is_empty() && last.comment().is_none() {
+                lines = init;
+            } else {
+                break;
+            }
+        }
+        let codes = lines.iter().map(CstLine::format_code).collect::<Vec<_>>();
+
+        let mut output = String::new();
+        let mut column_opt = None;
+        for (i, line) in lines.iter().enumerate() {
+            if line.is_empty() {
+                if let Some(comment) = line.comment() {
+                    output.push_str(&comment.text);
+                }
+                output.push('\n');
+                column_opt = None;
+                continue;
+            }
+            let code = &codes[i];
+            output.push_str(code);
+            if let Some(comment) = line.comment() {
+                let column = *column_opt
+                    .get_or_insert_with(|| Self::comment_column(&lines[i..], &codes[i..]));
+                let pad = column.saturating_sub(code.len()).max(1);
+                output.push_str(&" ".repeat(pad));
+                output.push_str(&comment.text);
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    // The column at which to align trailing comments in the block beginning at these lines
+    fn comment_column(lines: &[CstLine], codes: &[String]) -> usize {
+        lines
+            .iter()
+            .zip(codes.iter())
+            .take_while(|(line, _)| !line.is_empty())
+            .filter(|(line, code)| line.comment().is_some() && code.len() < COMMENT_COLUMN_MAX)
+            .map(|(_, code)| code.len() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Display for Cst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines = self.lines.iter();
+        if let Some(line) = lines.next() {
+            write!(f, "{}", line)?;
+        }
+        for line in lines {
+            write!(f, "\n{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let source =
+            "start:  \n  MOVE  r0   1.5E2 # a\r\n\nadd r1 r0 01.50  \n  # only a comment\nj start";
+        assert_eq!(Cst::parse(source).unwrap().to_string(), source);
+        assert_eq!(Cst::parse("").unwrap().to_string(), "");
+    }
+
+    #[test]
+    fn round_trip_scripts() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("test-scripts");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("mips".as_ref()) {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            let cst = Cst::parse(&source).unwrap();
+            assert_eq!(cst.to_string(), source, "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn format_comment_columns() {
+        // Blank and comment-only lines separate the blocks of aligned comments
+        let source = "\
+MOVE r0 1 # one
+add  r1   r0 r0 # two
+
+l r2 d0 Setting # three
+# a block break
+s d0 On 1 # four
+s d0 Setting 1
+";
+        let expected = "\
+move r0 1    # one
+add r1 r0 r0 # two
+
+l r2 d0 Setting # three
+# a block break
+s d0 On 1 # four
+s d0 Setting 1
+";
+        assert_eq!(Cst::parse(source).unwrap().format(), expected);
+    }
+
+    #[test]
+    fn format_long_lines() {
+        // Code too long for the comment column doesn't push the other comments out
+        let long = format!("s d0 Setting {}", "9".repeat(COMMENT_COLUMN_MAX));
+        let source = format!("move r0 1 # a\n{} # b\n\n\n", long);
+        let expected = format!("move r0 1 # a\n{} # b\n", long);
+        let formatted = Cst::parse(&source).unwrap().format();
+        assert_eq!(formatted, expected);
+        assert_eq!(Cst::parse(&formatted).unwrap().format(), formatted);
+    }
+}
@@ -3,20 +3,24 @@ use std::num::{ParseFloatError, ParseIntError};
 use std::{fmt, fmt::Display};
 
 use crate::{Alias, Pair, Rule};
-use ast_traits::AstErrorBase;
+use ast_common::constants::ConstError;
+use ast_traits::{peg_message, AstErrorBase, SourceSpan};
 
 type PegError = pest::error::Error<Rule>;
 
 #[derive(Debug)]
 pub enum MipsError {
-    PegError(PegError),
+    PegError(Box<PegError>),
     IOError(IOError),
     ParseIntError(ParseIntError),
     ParseFloatError(ParseFloatError),
 
     AstErrorBase(AstErrorBase),
+    ConstError(ConstError),
 
     LineError(String),
+    Syntax(String),
+    Unsupported(String),
 
     IndexInvalid(String),
     AliasUndefined(String),
@@ -24,6 +28,18 @@ pub enum MipsError {
     InstrUnknown(String),
     ArgsWrongNum(String),
     ArgsWrongKinds(String),
+
+    ConfigInvalid(String),
+    PresetUnknown(String),
+
+    TargetInvalid(String),
+    RegInvalid(String),
+    DevInvalid(String),
+    ScopeInvalid(String),
+    PassInvalid(String),
+
+    /// An error located in the source
+    Spanned(Box<MipsError>, Box<SourceSpan>),
 }
 
 // impl AstError for MipsError {
@@ -33,6 +49,67 @@ pub enum MipsError {
 // }
 
 impl MipsError {
+    /// A syntax error from parsing a source.
+    pub fn syntax(e: PegError, source: &str) -> Self {
+        let span = SourceSpan::from_peg_error(&e, source);
+        Self::Syntax(format!("Syntax error: {}", peg_message(&e))).at(span)
+    }
+
+    pub fn unsupported(what: &str) -> Self {
+        Self::Unsupported(format!("{} is not supported", what))
+    }
+
+    /// Locate the error at a span, unless it's already located.
+    pub fn at(self, span: SourceSpan) -> Self {
+        match self {
+            Self::Spanned(..) => self,
+            _ => Self::Spanned(Box::new(self), Box::new(span)),
+        }
+    }
+
+    /// Locate the error on a line of a source.
+    ///
+    /// An error already located by a single-line parse keeps its column, but is moved to the line.
+    pub fn on_line(self, line: usize, text: &str) -> Self {
+        match self {
+            Self::Spanned(e, span) => Self::Spanned(e, Box::new(SourceSpan { line, ..*span })),
+            _ => self.at(SourceSpan::line(line, text)),
+        }
+    }
+
+    /// Set the file of the span of a located error, unless it's already in a file.
+    pub fn in_file(self, file: &str) -> Self {
+        match self {
+            Self::Spanned(e, span) if span.file.is_none() => {
+                Self::Spanned(e, Box::new(span.with_file(file)))
+            }
+            _ => self,
+        }
+    }
+
+    /// Fill in the source line of a located error whose text isn't known.
+    pub fn with_source(self, source: &str) -> Self {
+        match self {
+            Self::Spanned(e, span) if span.text.is_none() => {
+                let text = source
+                    .lines()
+                    .nth(span.line.saturating_sub(1))
+                    .unwrap_or("");
+                let file = span.file;
+                let span = SourceSpan::line(span.line, text);
+                Self::Spanned(e, Box::new(SourceSpan { file, ..span }))
+            }
+            _ => self,
+        }
+    }
+
+    pub fn span(&self) -> Option<&SourceSpan> {
+        match self {
+            Self::Spanned(_, span) => Some(span.as_ref()),
+            _ => None,
+        }
+    }
+
     pub fn index_invalid<T: std::fmt::Display>(index: T) -> Self {
         Self::IndexInvalid(format!("Index '{}' is invalid", index))
     }
@@ -66,25 +143,72 @@ impl MipsError {
             name, expected, found,
         ))
     }
+
+    pub fn config_invalid(file: &str, e: impl Display) -> Self {
+        Self::ConfigInvalid(format!("Config {}: {}", file, e))
+    }
+
+    pub fn preset_unknown(name: &str, presets: &[&str]) -> Self {
+        Self::PresetUnknown(format!(
+            "Preset '{}' unknown, expected one of {}",
+            name,
+            presets.join(", ")
+        ))
+    }
+
+    pub fn target_invalid(target: f64) -> Self {
+        Self::TargetInvalid(format!("Jump target {} is outside of the program", target))
+    }
+
+    pub fn reg_invalid(index: usize) -> Self {
+        Self::RegInvalid(format!(
+            "Register r{} doesn't exist (the last is r15)",
+            index
+        ))
+    }
+
+    pub fn dev_invalid(index: usize) -> Self {
+        Self::DevInvalid(format!("Device d{} doesn't exist (the last is d5)", index))
+    }
+
+    pub fn scope_invalid(start: usize, end: usize) -> Self {
+        Self::ScopeInvalid(format!("Register scope ({},{}) is invalid", start, end))
+    }
+
+    pub fn pass_invalid(pass: &str, e: MipsError) -> Self {
+        Self::PassInvalid(format!("Pass '{}' left an invalid program: {}", pass, e))
+    }
 }
 
 impl Display for MipsError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::PegError(e) => write!(f, "{:?}", e),
-            Self::IOError(e) => write!(f, "{:?}", e),
+            Self::IOError(e) => write!(f, "{}", e),
             Self::ParseIntError(e) => write!(f, "{:?}", e),
             Self::ParseFloatError(e) => write!(f, "{:?}", e),
 
             Self::AstErrorBase(e) => write!(f, "{}", e),
+            Self::ConstError(e) => write!(f, "{}", e),
 
             Self::IndexInvalid(s)
             | Self::LineError(s)
+            | Self::Syntax(s)
+            | Self::Unsupported(s)
             | Self::AliasUndefined(s)
             | Self::AliasWrongKind(s)
             | Self::InstrUnknown(s)
             | Self::ArgsWrongNum(s)
-            | Self::ArgsWrongKinds(s) => write!(f, "{}", s),
+            | Self::ArgsWrongKinds(s)
+            | Self::ConfigInvalid(s)
+            | Self::PresetUnknown(s)
+            | Self::TargetInvalid(s)
+            | Self::RegInvalid(s)
+            | Self::DevInvalid(s)
+            | Self::ScopeInvalid(s)
+            | Self::PassInvalid(s) => write!(f, "{}", s),
+
+            Self::Spanned(e, span) => write!(f, "{}\n{}", e, span),
         }
     }
 }
@@ -101,11 +225,57 @@ macro_rules! impl_from_error {
     }
 }
 
+impl From<PegError> for MipsError {
+    fn from(e: PegError) -> Self {
+        Self::PegError(Box::new(e))
+    }
+}
+
 impl_from_error!(
     MipsError,
-    PegError,
     IOError,
     AstErrorBase,
+    ConstError,
     ParseIntError,
     ParseFloatError,
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn on_line_keeps_column() {
+        // As located by parsing the line on its own
+        let e = MipsError::unsupported("That").at(SourceSpan::new(1, 9, 1));
+        let e = e.on_line(4, "move r0 $");
+        let span = e.span().unwrap();
+        assert_eq!((span.line, span.col), (4, 9));
+
+        let e = MipsError::unsupported("That").on_line(2, "  yield");
+        assert_eq!(e.span(), Some(&SourceSpan::line(2, "  yield")));
+    }
+
+    #[test]
+    fn in_file_keeps_first_file() {
+        let e = MipsError::unsupported("That").in_file("a.mips");
+        assert_eq!(e.span(), None);
+
+        let e = MipsError::unsupported("That").at(SourceSpan::new(1, 1, 1));
+        let e = e.in_file("imported.mips").in_file("main.mips");
+        assert_eq!(e.span().unwrap().file.as_deref(), Some("imported.mips"));
+    }
+
+    #[test]
+    fn with_source_fills_line() {
+        let span = SourceSpan::new(2, 1, 1).with_file("a.mips");
+        let e = MipsError::unsupported("That").at(span);
+        let e = e.with_source("yield\n  sleep 1\n");
+        let expected = "That is not supported\n --> a.mips:2:3\n  |\n2 |   sleep 1\n  |   ^^^^^^^";
+        assert_eq!(e.to_string(), expected);
+
+        // A span whose text is known is left alone
+        let e = e.with_source("yield\nyield\n");
+        assert_eq!(e.span().unwrap().text.as_deref(), Some("  sleep 1"));
+    }
+}
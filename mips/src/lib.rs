@@ -4,19 +4,27 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
 use std::iter::IntoIterator;
 use std::path::PathBuf;
 
+use lazy_static::lazy_static;
 use pest::Parser;
 use pest_derive::Parser;
-use serde::{Deserialize, Serialize};
 
-use ast_traits::{AstNode, AstPairs, AstRule, IntoAst};
+use ast_traits::{AstNode, AstPairs, AstRule, IntoAst, SourceSpan};
 // use ast_common::AstCommonRule;
 
+pub mod cli;
+
+pub mod cst;
+
 pub mod graph;
-use graph::Graph;
+
+pub mod optimize;
+pub use optimize::{OptimizationConfig, OptimizationStats, Pass, PassManager, PassStats};
+
+pub mod verify;
 // use crate::mips::ast::IntoMipsNode;
 
 #[derive(Parser, Clone, Debug)]
@@ -98,20 +106,20 @@ impl Aliases {
         Self { map }
     }
 
+    /// Whether the key is an alias or a game constant.
     pub fn contains_key(&self, key: &str) -> bool {
-        // self.map.contains_key(key) // TODO: Stop-gap lowercase, until a constants table is implemented
-        self.map.contains_key(key) || self.map.contains_key(&key.to_lowercase())
+        self.map.contains_key(key) || CONSTANTS.contains_key(&key.to_lowercase())
     }
 
     pub fn insert(&mut self, key: String, alias: Alias) -> Option<Alias> {
         self.map.insert(key, alias)
     }
 
+    /// Get an alias, falling back to the game constants (matched case-insensitively).
     pub fn get(&self, key: &str) -> Option<&Alias> {
-        // self.map.get(key) // TODO: Stop-gap lowercase, until a constants table is implemented
         self.map
             .get(key)
-            .or_else(|| self.map.get(&key.to_lowercase()))
+            .or_else(|| CONSTANTS.get(&key.to_lowercase()))
     }
 
     pub fn try_get(&self, key: &str) -> MipsResult<&Alias> {
@@ -119,7 +127,7 @@ impl Aliases {
     }
 
     pub fn try_get_dev_base(&self, key: &str) -> MipsResult<DevBase> {
-        let alias = self.try_get(key).unwrap();
+        let alias = self.try_get(key)?;
         match alias {
             Alias::Dev(dev_lit) => Ok(dev_lit.clone()),
             _ => Err(MipsError::alias_wrong_kind("a device", alias)),
@@ -127,14 +135,14 @@ impl Aliases {
     }
 
     pub fn get_reg_base(&self, key: &str) -> MipsResult<Option<RegBase>> {
-        match self.try_get(key).unwrap() {
+        match self.try_get(key)? {
             Alias::Reg(reg_lit) => Ok(Some(reg_lit.clone())),
             _ => Ok(None),
         }
     }
 
     pub fn try_get_reg_base(&self, key: &str) -> MipsResult<RegBase> {
-        let alias = self.try_get(key).unwrap();
+        let alias = self.try_get(key)?;
         match alias {
             Alias::Reg(reg) => Ok(reg.clone()),
             _ => Err(MipsError::alias_wrong_kind("a register", alias)),
@@ -142,32 +150,32 @@ impl Aliases {
     }
 }
 
-// TODO: Probably going to want to have a separate table for constants
-// TODO: REALLY need to add a constants map!
 impl Default for Aliases {
     fn default() -> Self {
         let map = maplit::btreemap! {
             "db".into() => Alias::Dev(DevBase::DB),
             "sp".into() => Alias::Reg(RegBase::SP),
             "ra".into() => Alias::Reg(RegBase::RA),
-            // Back read modes
-            "average".into() => Alias::Num(0.0),
-            "avg".into() => Alias::Num(0.0),
-            "sum".into() => Alias::Num(1.0),
-            "min".into() => Alias::Num(2.0),
-            "max".into() => Alias::Num(3.0),
-            // Reagent modes
-            "contents".into() => Alias::Num(0.0),
-            "required".into() => Alias::Num(1.0),
-            "recipe".into() => Alias::Num(2.0),
-
-            "horizontal".into() => Alias::Num(20.0),
-            "vertical".into() => Alias::Num(21.0),
         };
         Self { map }
     }
 }
 
+lazy_static! {
+    // Game constants which can be used as numbers (batch modes, reagent modes and prefab
+    // hashes), keyed by lowercased name
+    static ref CONSTANTS: BTreeMap<String, Alias> = {
+        use ast_common::constants::{BATCH_MODES, PREFABS, REAGENT_MODES};
+
+        BATCH_MODES
+            .iter_lowercase()
+            .chain(REAGENT_MODES.iter_lowercase())
+            .chain(PREFABS.iter_lowercase())
+            .map(|(key, value)| (key.to_owned(), Alias::Num(value as f64)))
+            .collect()
+    };
+}
+
 #[derive(Clone, Debug)]
 pub struct Mips {
     //     // pub registers: Vec<RegShared>,
@@ -202,6 +210,14 @@ impl Default for Mips {
     }
 }
 
+impl std::str::FromStr for Mips {
+    type Err = Vec<MipsError>;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
 // #[derive(Clone, Debug)]
 // pub struct Lifetime {
 //     index: usize,
@@ -210,79 +226,86 @@ impl Default for Mips {
 // }
 
 impl Mips {
-    pub fn lex_file<P: Into<PathBuf> + std::fmt::Debug>(path: P) -> Result<Self, String> {
-        let path_string = format!("{:?}", path);
-        let f = File::open(path.into()).expect(&path_string);
-        let f = BufReader::new(f);
-        let lines = f.lines().collect::<Result<Vec<_>, _>>().unwrap();
-        // lex_lines(lines.into_iter())
+    pub fn lex_file<P: Into<PathBuf> + std::fmt::Debug>(path: P) -> Result<Self, Vec<MipsError>> {
+        let path = path.into();
+        let f = File::open(&path).map_err(|e| vec![e.into()])?;
+        let file = path.to_string_lossy();
+        Self::from_reader(f).map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|e| e.in_file(&file))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    /// Parse a source, returning the errors of all lines.
+    pub fn parse(source: &str) -> Result<Self, Vec<MipsError>> {
         let mut mips = Self::default();
-        mips.parse_lines(lines).unwrap();
+        mips.parse_lines(source.lines().map(str::to_owned))?;
         Ok(mips)
     }
 
-    // pub fn default_with_source(source: &str) -> Result<Self, String> {
-    //     let mut mips = Self::default();
-    //     mips.parse_source(source)?;
-    //     // mips.scopes.push(0..mips.lines.len());
-    //     mips.lex()?;
-    //     Ok(mips)
-    // }
+    /// Parse a source from a reader, returning the errors of all lines (or the read error).
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Vec<MipsError>> {
+        let lines = BufReader::new(reader)
+            .lines()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| vec![e.into()])?;
+        let mut mips = Self::default();
+        mips.parse_lines(lines)?;
+        Ok(mips)
+    }
 
-    pub fn default_with_lines(lines: Vec<Line>) -> Result<Self, String> {
+    /// Build a program from lines, returning the errors of all lines.
+    pub fn default_with_lines(lines: Vec<Line>) -> Result<Self, Vec<MipsError>> {
         let mut mips = Self::default();
         mips.lines.extend(lines);
         // mips.scopes = scopes;
-        mips.lex().unwrap();
+        mips.lex()?;
         Ok(mips)
     }
 
-    fn parse_line(&mut self, line: &str) -> Result<(), String> {
-        let pairs = MipsParser::parse(Rule::line, line)
-            .map_err(|err| {
-                format!(
-                    "(MIPS error) Line parsing error\nLine: \"{}\"\nError: {:?}",
-                    line, err
-                )
-            })
-            .unwrap();
+    // Parse a source line as the next line, locating any error on it
+    fn parse_line(&mut self, source: &str) -> MipsResult<()> {
+        let n = self.lines.len() + 1;
 
-        let line_pair = pairs
+        let line_pair = MipsParser::parse(Rule::line, source)
+            .map_err(|e| MipsError::syntax(e, source).on_line(n, source))?
             .only_pair()
-            .map_err(|err| {
-                format!(
-                    "(MIPS error) Too many inner pairs\nLine: \"{}\"\nError: {:?}",
-                    line, err
-                )
-            })
-            .unwrap();
-
-        let line = line_pair
+            .map_err(|e| MipsError::from(e).on_line(n, source))?;
+
+        let mut line = line_pair
             .try_into_ast::<Line>()
-            .map_err(|err| {
-                format!(
-                    "(MIPS error) Instruction parsing error\nLine: \"{}\"\nError: {}",
-                    line, err
-                )
-            })
-            .unwrap();
+            .map_err(|e| e.on_line(n, source))?;
+        line.source_line = Some(n);
 
         self.lines.push(line);
         Ok(())
     }
 
-    fn parse_lines<I: IntoIterator<Item = String>>(&mut self, lines: I) -> Result<(), String> {
+    // Parse source lines, returning the errors of all lines. A line with an error is parsed as
+    // an empty line so that the lines after it keep their numbers.
+    fn parse_lines<I: IntoIterator<Item = String>>(
+        &mut self,
+        lines: I,
+    ) -> Result<(), Vec<MipsError>> {
+        let mut errors = Vec::new();
         for line in lines.into_iter() {
-            self.parse_line(&line).unwrap();
+            if let Err(e) = self.parse_line(&line) {
+                errors.push(e);
+                self.lines.push(Line::new_no_comment(Stmt::Empty([])));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
-        Ok(())
     }
 
-    // fn parse_source(&mut self, source: &str) -> Result<(), String> {
-    //     self.parse_lines(source.trim_end().split("\n"))
-    // }
-
-    fn lex(&mut self) -> Result<(), String> {
+    // Lex the lines, returning the errors of all lines. A line with an error is skipped so that
+    // the lines after it are still checked.
+    fn lex(&mut self) -> Result<(), Vec<MipsError>> {
         // Line tag pass
         for (i, line) in self.lines.iter().enumerate() {
             let Line { stmt, .. } = line;
@@ -293,19 +316,17 @@ impl Mips {
             }
         }
         // Variable pass
+        let mut errors = Vec::new();
         for i in 0..self.lines.len() {
-            self.lex_line(i).unwrap();
-            // self.lex_line(i)
-            //     .map_err(|err| {
-            //         let line = &self.lines[i];
-            //         format!(
-            //             "(MIPS lexer error) Line {}\nLine: {}\nError: {}",
-            //             i, line, err
-            //         )
-            //     })
-            //     .unwrap();
+            if let Err(e) = self.lex_line(i) {
+                errors.push(e.on_line(i + 1, &self.lines[i].to_string()));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
-        Ok(())
     }
 
     fn lex_line(&mut self, i: usize) -> MipsResult<()> {
@@ -318,19 +339,22 @@ impl Mips {
                 | Arg::LineAbs(LineAbs(Num::Alias(key)))
                 | Arg::LineRel(LineRel(Num::Alias(key))) => {
                     if !self.aliases.contains_key(key) {
-                        return Err(MipsError::alias_undefined(key));
+                        let span = SourceSpan::token(i + 1, &self.lines[i].to_string(), key);
+                        return Err(MipsError::alias_undefined(key).at(span));
                     }
                 }
                 _ => {}
             }
         }
         // Insert aliases and definitions
-        let Line { stmt, comment_opt } = &mut self.lines[i];
+        let Line {
+            stmt, comment_opt, ..
+        } = &mut self.lines[i];
         match stmt {
             Stmt::Alias([Arg::String(key), Arg::Reg(reg)]) => {
                 let mut reg_base = match reg {
                     Reg::Base(reg_base) => reg_base.clone(),
-                    Reg::Alias { key, .. } => self.aliases.try_get_reg_base(key).unwrap(),
+                    Reg::Alias { key, .. } => self.aliases.try_get_reg_base(key)?,
                 };
                 if let Some(comment) = comment_opt {
                     if comment.contains("FIX") {
@@ -344,7 +368,7 @@ impl Mips {
             Stmt::Alias([Arg::String(key), Arg::Dev(dev)]) => {
                 let dev_base = match dev {
                     Dev::Base(dev_base) => dev_base.clone(),
-                    Dev::Alias(key) => self.aliases.try_get_dev_base(key).unwrap(),
+                    Dev::Alias(key) => self.aliases.try_get_dev_base(key)?,
                 };
                 let alias = Alias::Dev(dev_base);
                 self.aliases.insert(key.clone(), alias);
@@ -368,220 +392,32 @@ impl Mips {
         Ok(())
     }
 
-    pub fn optimize(&self, conf: OptimizationConfig) -> Result<Mips, String> {
-        let mut mips = self.clone();
-
-        let tag_lines = mips
-            .lines
-            .iter()
-            .enumerate()
-            .filter_map(|(i, line)| {
-                if let Stmt::Tag([key]) = &line.stmt {
-                    let a = Some((key.to_string(), i));
-                    a
-                } else {
-                    None
-                }
-            })
-            .collect::<BTreeMap<String, usize>>();
-
-        mips.lex().unwrap();
-
-        if conf.optimize_registers {
-            let lifetimes = mips.analyze_lifetimes();
-            let n = lifetimes.len();
-            let node_iter = lifetimes
-                .iter()
-                .map(|(index, _)| *index)
-                .collect::<BTreeSet<usize>>()
-                .into_iter()
-                .map(|index| (index, index));
-            let edge_iter = (0..n)
-                .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
-                .filter_map(|(i, j)| {
-                    let (i_index, (i_s, i_e)) = &lifetimes[i];
-                    let (j_index, (j_s, j_e)) = &lifetimes[j];
-                    ((i_s < j_e) && (j_s < i_e)).then_some((*i_index, *j_index))
-                });
-            let graph = Graph::from_edges(node_iter.chain(edge_iter)).color();
-            let colors = graph
-                .into_nodes()
-                .map(|node| {
-                    let index = node.index();
-                    let color = node.color().unwrap();
-                    (index, color)
-                })
-                .collect::<BTreeMap<_, _>>();
-            for line in mips.lines.iter_mut() {
-                for arg in line.stmt.iter_args_mut() {
-                    if let Arg::Dev(Dev::Base(DevBase::Lit(dev_lit))) = arg {
-                        if dev_lit.indirections > 0 {
-                            if let Some(new_index) = colors.get(&dev_lit.index) {
-                                dev_lit.index = *new_index;
-                            }
-                        }
-                    } else if let Some(RegBase::Lit(reg_lit)) = arg.as_reg_base_mut() {
-                        if let Some(new_index) = colors.get(&reg_lit.index) {
-                            reg_lit.index = *new_index;
-                        }
-                    }
-                }
-            }
-        }
-
-        // Define/alias/tag replacement and comment removal pass
-        for (_i, line) in mips.lines.iter_mut().enumerate() {
-            if conf.remove_comments {
-                line.comment_opt = None;
-            }
-            match line {
-                // Replace defines
-                Line {
-                    stmt: Stmt::Define(..),
-                    comment_opt,
-                } if conf.remove_defines => {
-                    let comment_opt = comment_opt.take();
-                    *line = Line {
-                        stmt: Stmt::Empty([]),
-                        comment_opt,
-                    };
-                }
-                // Replace aliases
-                Line {
-                    stmt: Stmt::Alias([_, rhs], ..),
-                    comment_opt,
-                } if matches!(rhs, Arg::Dev(..)) && conf.remove_dev_aliases
-                    || matches!(rhs, Arg::Reg(..)) && conf.remove_reg_aliases =>
-                {
-                    let comment_opt = comment_opt.take();
-                    *line = Line {
-                        stmt: Stmt::Empty([]),
-                        comment_opt,
-                    };
-                }
-                // Replace tags
-                Line {
-                    stmt: Stmt::Tag(_),
-                    comment_opt,
-                } if conf.remove_tags => {
-                    let comment_opt = comment_opt.take();
-                    *line = Line {
-                        stmt: Stmt::Empty([]),
-                        comment_opt,
-                    };
-                }
-                Line { .. } => {}
-            }
-        }
-        // Argument and comment replacement pass
-        for line in mips.lines.iter_mut() {
-            for arg in line.stmt.iter_args_mut() {
-                match arg {
-                    Arg::LineAbs(LineAbs(num)) if conf.remove_tags => {
-                        if let Some(key) = num.as_alias() {
-                            let i = tag_lines.get(key).expect(key);
-                            *num = Num::Lit(*i as f64);
-                        }
-                    }
-                    Arg::Num(num) => {
-                        if let Some(key) = num.as_alias() {
-                            if let Some(alias) = mips.aliases.get(key) {
-                                match alias {
-                                    Alias::Num(n) => {
-                                        if conf.remove_defines {
-                                            *arg = Arg::Num(Num::Lit(*n));
-                                        }
-                                    }
-                                    Alias::Reg(reg_base) => {
-                                        if conf.remove_reg_aliases {
-                                            *arg = Arg::Reg(Reg::Base(*reg_base));
-                                        }
-                                    }
-                                    Alias::Dev(_) => {
-                                        panic!();
-                                    }
-                                }
-                            }
-                            // *num = Num::Lit()
-                        }
-                    }
-                    Arg::Reg(Reg::Alias { key, .. }) => {
-                        if conf.remove_reg_aliases {
-                            let reg_base = mips.aliases.try_get_reg_base(key).unwrap();
-                            *arg = Arg::Reg(Reg::Base(reg_base));
-                        }
-                    }
-                    Arg::Dev(Dev::Alias(key)) => {
-                        if conf.remove_dev_aliases {
-                            let dev_base = mips.aliases.try_get_dev_base(key).unwrap();
-                            *arg = Arg::Dev(Dev::Base(dev_base));
-                        }
-                    }
-                    // Arg::String(key) => {
-                    // }
-                    _ => {}
-                }
-            }
-        }
-        // Remove empty lines
-        if conf.remove_empty || conf.remove_empty_comments {
-            let mut i = 0;
-            while i < mips.lines.len() {
-                let Line { stmt, comment_opt } = &mut mips.lines[i];
-                match stmt {
-                    Stmt::Empty(..)
-                        if (conf.remove_empty
-                            && (comment_opt.is_none() || conf.remove_empty_comments)) =>
-                    {
-                        mips.remove_line(i);
-                    }
-                    _ => i += 1,
-                }
-            }
-        }
-        // // Remove extraneous instructions
-        // let mut i = 0;
-        // while i < mips.lines.len() {
-        //     let Line { stmt, comment_opt } = &mut mips.lines[i];
-        //     match stmt {
-        //         Stmt::Empty(..)
-        //             if (conf.remove_empty
-        //                 && (conf.remove_empty_comments || comment_opt.is_none())) =>
-        //         {
-        //             mips.remove_line(i);
-        //         }
-        //         Stmt::Define([Arg::String(key), ..], ..) if conf.remove_defines => {
-        //             mips.present_aliases.remove(key);
-        //             mips.remove_line(i);
-        //         }
-        //         Stmt::Alias([Arg::String(key), rhs], ..)
-        //             if matches!(rhs, Arg::Dev(..)) && conf.remove_dev_aliases
-        //                 || matches!(rhs, Arg::Reg(..)) && conf.remove_reg_aliases =>
-        //         {
-        //             mips.present_aliases.remove(key);
-        //             mips.remove_line(i);
-        //         }
-        //         Stmt::Tag([Arg::String(key)], ..) if conf.remove_tags => {
-        //             let alias = Alias::Num(i as f64);
-        //             mips.aliases.insert(key.clone(), alias);
-        //             mips.present_aliases.remove(key);
-        //             mips.remove_line(i);
-        //         }
-        //         _ => i += 1,
-        //     }
-        // }
+    pub fn optimize(&self, conf: OptimizationConfig) -> Result<Mips, Vec<MipsError>> {
+        Ok(self.optimize_with_stats(conf)?.0)
+    }
 
-        // // Re-lex
-        // mips.lex_lines()?;
+    /// Optimize, also reporting the statistics of each pass (or all errors of the program).
+    pub fn optimize_with_stats(
+        &self,
+        conf: OptimizationConfig,
+    ) -> Result<(Mips, OptimizationStats), Vec<MipsError>> {
+        PassManager::new(conf).run(self)
+    }
 
-        // // Reduce instructions
-        // let mut lines = mips.lines.clone();
-        // for line in lines.iter_mut() {
-        //     line.stmt.reduce_args(&mips.aliases).ok();
-        // }
-        // mips.lines = lines;
+    /// The lines as source text.
+    pub fn emit(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
 
-        Ok(mips)
+    /// The lines as the shortest equivalent source text (numbers in their shortest form).
+    pub fn emit_min(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| format!("{:#}\n", line))
+            .collect()
     }
 
     /// Remove a line safely
@@ -591,6 +427,8 @@ impl Mips {
     pub fn remove_line(&mut self, i: usize) {
         use crate::ast::FixMode;
 
+        // A scope ending at the last line ends at the new last line
+        let last = self.lines.len() - 1;
         // Adjust relative branches encompassing the line to remove
         for (j, line) in self.lines.iter_mut().enumerate().filter(|&(j, _)| i != j) {
             for arg in line.stmt.iter_args_mut() {
@@ -599,7 +437,7 @@ impl Mips {
                         if *s > i {
                             *s -= 1;
                         }
-                        if *e > i {
+                        if *e > i || (*e == i && i == last && i > 0) {
                             *e -= 1;
                         }
                     }
@@ -744,6 +582,13 @@ impl Mips {
         res
     }
 
+    /// The number of distinct registers with a lifetime.
+    pub fn num_registers(&self) -> usize {
+        let lifetimes = self.analyze_lifetimes();
+        let indices = lifetimes.iter().map(|(index, _)| *index);
+        indices.collect::<BTreeSet<_>>().len()
+    }
+
     pub fn interference_graph(&self) -> String {
         let lifetimes = self.analyze_lifetimes();
 
@@ -775,30 +620,62 @@ impl Mips {
     }
 }
 
-#[allow(dead_code)]
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct OptimizationConfig {
-    pub remove_comments: bool,
-    pub remove_empty: bool,
-    pub remove_empty_comments: bool,
-    pub remove_reg_aliases: bool,
-    pub remove_dev_aliases: bool,
-    pub remove_defines: bool,
-    pub remove_tags: bool,
-    pub optimize_registers: bool,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl Default for OptimizationConfig {
-    fn default() -> Self {
-        Self {
-            remove_comments: true,
-            remove_empty: true,
-            remove_empty_comments: true,
-            remove_reg_aliases: true,
-            remove_dev_aliases: true,
-            remove_defines: true,
-            remove_tags: true,
-            optimize_registers: true,
-        }
+    const SOURCE: &str = "define x 3\nalias a r0\n\nloop: # Wait\nmove a x\ns d0 On a\nj loop\n";
+
+    fn text(mips: &Mips) -> String {
+        mips.lines
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    #[test]
+    fn constructors_round_trip() {
+        let parsed = Mips::parse(SOURCE).unwrap();
+        assert_eq!(text(&parsed), SOURCE);
+        let from_str = SOURCE.parse::<Mips>().unwrap();
+        assert_eq!(text(&from_str), SOURCE);
+        let from_reader = Mips::from_reader(SOURCE.as_bytes()).unwrap();
+        assert_eq!(text(&from_reader), SOURCE);
+    }
+
+    #[test]
+    fn batch_mode_arg() {
+        let mips = Mips::parse("define h 1234\nlb r0 h temperature max\ns d0 Setting r0\n");
+        let mips = Mips::default_with_lines(mips.unwrap().lines).unwrap();
+        assert_eq!(mips.lines[1].to_string(), "lb r0 h Temperature Maximum");
+        // The mode is written as its number once optimized
+        let optimized = mips.optimize(OptimizationConfig::default()).unwrap();
+        let expected = "lb r0 1234 Temperature 3\ns d0 Setting r0\n";
+        assert_eq!(text(&optimized), expected);
+    }
+
+    #[test]
+    fn from_reader_errors() {
+        // A read error is the only error
+        let errors = Mips::from_reader(&b"move r0 1\n\xff\n"[..]).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], MipsError::IOError(..)), "{:?}", errors);
+        // Otherwise the errors are of all lines
+        let errors = Mips::from_reader("move r0 $\nadd r0\n".as_bytes()).unwrap_err();
+        let lines = errors.iter().map(|e| e.span().unwrap().line);
+        assert_eq!(lines.collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn lex_errors_of_all_lines() {
+        let mips = Mips::parse("move r0 foo\nadd r1 r0 1\ns d0 Setting bar\n").unwrap();
+        let errors = Mips::default_with_lines(mips.lines).unwrap_err();
+        let lines = errors
+            .iter()
+            .map(|e| e.span().unwrap().line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [1, 3]);
+        assert!(errors[0].to_string().contains("Alias foo undefined"));
+        assert!(errors[1].to_string().contains("Alias bar undefined"));
     }
 }
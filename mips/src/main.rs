@@ -1,71 +1,210 @@
-#![feature(stmt_expr_attributes)]
-#![feature(bool_to_option)]
-#![feature(box_patterns)]
-#![feature(array_methods)]
-#![allow(unused_imports)]
-#![allow(unused_mut)]
-#![allow(dead_code)]
+use std::io::{Read, Write};
 
-use pest::Parser;
+use mips::ast::Stmt;
+use mips::cli::Failure;
+use mips::{Mips, MipsError, OptimizationConfig};
 
-use ast_traits::*;
+fn usage() -> String {
+    format!(
+        "\
+usage: mips <command> [options] [path]
 
-use mips::ast::*;
-use mips::*;
+Work with a MIPS program. The program is read from stdin if there's no path or the path is `-`.
 
-fn main() {
-    let path = std::env::args().skip(1).next().unwrap();
-    // let source = std::fs::read_to_string(path).unwrap();
+commands:
+    check                       parse and validate the program, printing any errors
+    fmt                         format the program
+    optimize                    optimize the program
+    lifetimes                   print the lifetimes of the registers of the program
+    stats                       print statistics of the program and of its optimization
 
-    // let path_string = format!("{:?}", path);
-    // let f = File::open(path.into()).expect(&path_string);
-    // let f = BufReader::new(f);
-    // let lines = f.lines().collect::<Result<Vec<_>, _>>().unwrap();
+options:
+    -o, --output <path>         write the output to a file rather than stdout
+    -h, --help                  print this message
 
-    let mips = Mips::lex_file(path).unwrap();
-    // println!("{:#?}", mips.lines);
+optimization options (optimize, stats):
+{}",
+        mips::cli::optimization_usage().trim_end()
+    )
+}
 
-    // println!("================================================================================");
-    let w = (mips.lines.len() as f64 - 1.0).log10().floor().max(0_f64) as usize + 1;
-    for (i, line) in mips.lines.iter().enumerate() {
-        println!("{:>w$}: {:?}", i, line, w = w);
-    }
-    println!("--------------------------------------------------------------------------------");
-    for (i, line) in mips.lines.iter().enumerate() {
-        println!("{:>w$}: {}", i, line, w = w);
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Command {
+    Check,
+    Fmt,
+    Optimize,
+    Lifetimes,
+    Stats,
+}
+
+impl std::str::FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "check" => Ok(Self::Check),
+            "fmt" => Ok(Self::Fmt),
+            "optimize" => Ok(Self::Optimize),
+            "lifetimes" => Ok(Self::Lifetimes),
+            "stats" => Ok(Self::Stats),
+            _ => Err(format!("unknown command '{}'", s)),
+        }
     }
+}
+
+#[derive(Debug)]
+struct Args {
+    help: bool,
+    command: Option<Command>,
+    path: Option<String>,
+    output: Option<String>,
+    conf: OptimizationConfig,
+}
+
+impl Args {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, Failure> {
+        let mut help = false;
+        let mut command = None;
+        let mut path = None;
+        let mut output = None;
 
-    println!("================================================================================");
-    let mips = mips
-        .optimize(OptimizationConfig {
-            optimize_registers: true,
-            // optimize_registers: false,
-            // remove_comments: true,
-            remove_comments: false,
-            remove_empty: true,
-            // remove_empty: false,
-            // remove_empty_comments: true,
-            remove_empty_comments: false,
-
-            remove_reg_aliases: true,
-            // remove_reg_aliases: false,
-
-            remove_dev_aliases: true,
-            // remove_dev_aliases: false,
-            remove_defines: true,
-            // remove_defines: false,
-            remove_tags: true,
-            // remove_tags: false,
+        let conf = mips::cli::parse_args(args, |arg, value| {
+            match arg {
+                "-h" | "--help" => help = true,
+                "-o" | "--output" => output = Some(value()?),
+                "-" => path = Some(arg.to_owned()),
+                _ if arg.starts_with('-') => {
+                    return Err(Failure::usage(format!("unknown option '{}'", arg)))
+                }
+                _ if command.is_none() => {
+                    command = Some(arg.parse::<Command>().map_err(Failure::usage)?)
+                }
+                _ if path.is_none() => path = Some(arg.to_owned()),
+                _ => return Err(Failure::usage(format!("unexpected argument '{}'", arg))),
+            }
+            Ok(())
+        })?;
+        Ok(Self {
+            help,
+            command,
+            path: path.filter(|path| path != "-"),
+            output,
+            conf,
         })
-        .unwrap();
-    let w = (mips.lines.len() as f64 - 1.0).log10().floor().max(0_f64) as usize + 1;
-    for (i, line) in mips.lines.iter().enumerate() {
-        println!("{:>w$}: {:?}", i, line, w = w);
     }
-    println!("--------------------------------------------------------------------------------");
-    #[allow(unused_variables)]
-    for (i, line) in mips.lines.iter().enumerate() {
-        // println!("{:>w$}: {}", i, line, w = w);
-        println!("{}", line);
+
+    // The name of the input, for locating errors
+    fn file(&self) -> &str {
+        self.path.as_deref().unwrap_or("<stdin>")
+    }
+}
+
+// The failure of errors of a file
+fn fail_in_file(errors: Vec<MipsError>, file: &str) -> Failure {
+    Failure::all(errors.into_iter().map(|e| e.in_file(file)).collect())
+}
+
+// Parse and lex a source, failing with its errors if it's invalid
+fn lex(source: &str, file: &str) -> Result<Mips, Failure> {
+    let mips = Mips::parse(source).map_err(|errors| fail_in_file(errors, file))?;
+    Mips::default_with_lines(mips.lines).map_err(|errors| fail_in_file(errors, file))
+}
+
+// The number of non-empty statements
+fn num_stmts(mips: &Mips) -> usize {
+    let stmts = mips.lines.iter().map(|line| &line.stmt);
+    stmts
+        .filter(|stmt| !matches!(stmt, Stmt::Empty(..)))
+        .count()
+}
+
+fn stats(mips: &Mips, conf: OptimizationConfig, file: &str) -> Result<String, Failure> {
+    let (optimized, optimization) = mips
+        .optimize_with_stats(conf)
+        .map_err(|errors| fail_in_file(errors, file))?;
+
+    // Counts before and after optimization
+    #[rustfmt::skip]
+    let counts: [(&str, fn(&Mips) -> usize); 6] = [
+        ("lines",       |mips| mips.lines.len()),
+        ("statements",  num_stmts),
+        ("registers",   Mips::num_registers),
+        ("aliases",     |mips| mips.present_aliases.len().saturating_sub(mips.tags.len())),
+        ("tags",        |mips| mips.tags.len()),
+        ("chars",       |mips| mips.emit().len()),
+    ];
+    let mut output = String::new();
+    for (name, count) in counts.iter() {
+        output.push_str(&format!(
+            "{}: {} -> {}\n",
+            name,
+            count(mips),
+            count(&optimized)
+        ));
+    }
+    output.push_str(&format!("{}\n", optimization));
+    Ok(output)
+}
+
+fn run() -> Result<(), Failure> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    if args.help {
+        println!("{}", usage());
+        return Ok(());
+    }
+    let command = args
+        .command
+        .ok_or_else(|| Failure::usage("expected a command"))?;
+
+    let source = match &args.path {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| Failure::new(format!("couldn't read {}: {}", path, e)))?,
+        None => {
+            let mut source = String::new();
+            std::io::stdin()
+                .read_to_string(&mut source)
+                .map_err(|e| Failure::new(format!("couldn't read stdin: {}", e)))?;
+            source
+        }
+    };
+    let file = args.file();
+
+    let output = match command {
+        Command::Check => {
+            let mips = lex(&source, file)?;
+            mips.verify().map_err(|errors| fail_in_file(errors, file))?;
+            String::new()
+        }
+        Command::Fmt => {
+            let cst = mips::cst::Cst::parse(&source).map_err(|e| Failure::new(e.in_file(file)))?;
+            cst.format()
+        }
+        Command::Optimize => {
+            let minimize_size = args.conf.minimize_size;
+            let mips = lex(&source, file)?
+                .optimize(args.conf.clone())
+                .map_err(|errors| fail_in_file(errors, file))?;
+            if minimize_size {
+                mips.emit_min()
+            } else {
+                mips.emit()
+            }
+        }
+        Command::Lifetimes => lex(&source, file)?.interference_graph(),
+        Command::Stats => stats(&lex(&source, file)?, args.conf.clone(), file)?,
+    };
+    match &args.output {
+        Some(path) => std::fs::write(path, output)
+            .map_err(|e| Failure::new(format!("couldn't write {}: {}", path, e))),
+        None => std::io::stdout()
+            .write_all(output.as_bytes())
+            .map_err(Failure::new),
+    }
+}
+
+fn main() {
+    if let Err(failure) = run() {
+        eprintln!("{}", failure);
+        std::process::exit(failure.exit_code());
     }
 }
@@ -0,0 +1,766 @@
+//! The optimizer: its config, its passes and the manager running them.
+//!
+//! The passes run in the order of [`Pass::ALL`]. The cleanup passes ([`Pass::is_cleanup`]) run
+//! last, and repeatedly until they don't change the program, since one can leave work for
+//! another.
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::{fmt, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{Arg, Dev, DevBase, Line, LineAbs, MipsNode, Num, Reg, RegBase, Stmt};
+use crate::graph::Graph;
+use crate::{Alias, Mips, MipsError, MipsResult};
+
+/// What to optimize.
+///
+/// A config can be read from a RON or TOML file ([`OptimizationConfig::from_file`]), in which
+/// missing fields take their default value, or be one of the named presets
+/// ([`OptimizationConfig::preset`]).
+#[allow(dead_code)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OptimizationConfig {
+    pub remove_comments: bool,
+    pub remove_empty: bool,
+    pub remove_empty_comments: bool,
+    pub remove_reg_aliases: bool,
+    pub remove_dev_aliases: bool,
+    pub remove_defines: bool,
+    pub remove_tags: bool,
+    pub optimize_registers: bool,
+    /// Keep defines which are shorter than their inlined uses, and emit shortest numbers
+    pub minimize_size: bool,
+}
+
+impl Default for OptimizationConfig {
+    fn default() -> Self {
+        Self {
+            remove_comments: true,
+            remove_empty: true,
+            remove_empty_comments: true,
+            remove_reg_aliases: true,
+            remove_dev_aliases: true,
+            remove_defines: true,
+            remove_tags: true,
+            optimize_registers: true,
+            minimize_size: false,
+        }
+    }
+}
+
+impl OptimizationConfig {
+    /// The names of the presets.
+    pub const PRESETS: [&'static str; 3] = ["size", "readable", "debug"];
+
+    /// A named preset:
+    /// * `size`: every optimization, for the shortest output
+    /// * `readable`: keep tags, aliases, defines, comments and empty lines, but still allocate
+    ///   registers
+    /// * `debug`: no rewriting at all
+    pub fn preset(name: &str) -> MipsResult<Self> {
+        let all = |on: bool| Self {
+            remove_comments: on,
+            remove_empty: on,
+            remove_empty_comments: on,
+            remove_reg_aliases: on,
+            remove_dev_aliases: on,
+            remove_defines: on,
+            remove_tags: on,
+            optimize_registers: on,
+            minimize_size: on,
+        };
+        match name {
+            "size" => Ok(all(true)),
+            "readable" => Ok(Self {
+                optimize_registers: true,
+                ..all(false)
+            }),
+            "debug" => Ok(all(false)),
+            _ => Err(MipsError::preset_unknown(name, &Self::PRESETS)),
+        }
+    }
+
+    /// Read a config from a file, as TOML if it has a `.toml` extension and as RON otherwise.
+    pub fn from_file<P: Into<PathBuf>>(path: P) -> MipsResult<Self> {
+        let path = path.into();
+        let file = path.to_string_lossy();
+        let text =
+            std::fs::read_to_string(&path).map_err(|e| MipsError::config_invalid(&file, e))?;
+        let is_toml = path.extension().map_or(false, |ext| ext == "toml");
+        if is_toml {
+            toml::from_str(&text).map_err(|e| MipsError::config_invalid(&file, e))
+        } else {
+            ron::from_str(&text).map_err(|e| MipsError::config_invalid(&file, e))
+        }
+    }
+
+    /// The names of the fields, which are all switches.
+    pub const FIELDS: [&'static str; 9] = [
+        "remove_comments",
+        "remove_empty",
+        "remove_empty_comments",
+        "remove_reg_aliases",
+        "remove_dev_aliases",
+        "remove_defines",
+        "remove_tags",
+        "optimize_registers",
+        "minimize_size",
+    ];
+
+    /// The switch of a field by name.
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "remove_comments" => Some(&mut self.remove_comments),
+            "remove_empty" => Some(&mut self.remove_empty),
+            "remove_empty_comments" => Some(&mut self.remove_empty_comments),
+            "remove_reg_aliases" => Some(&mut self.remove_reg_aliases),
+            "remove_dev_aliases" => Some(&mut self.remove_dev_aliases),
+            "remove_defines" => Some(&mut self.remove_defines),
+            "remove_tags" => Some(&mut self.remove_tags),
+            "optimize_registers" => Some(&mut self.optimize_registers),
+            "minimize_size" => Some(&mut self.minimize_size),
+            _ => None,
+        }
+    }
+}
+
+/// A pass of the optimizer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Pass {
+    /// Allocate registers by coloring the interference graph of their lifetimes
+    Registers,
+    /// Remove comments
+    Comments,
+    /// Replace the uses of defines, aliases and tags by their values, and empty their lines
+    Aliases,
+    /// Remove empty lines
+    EmptyLines,
+}
+
+impl Pass {
+    /// The passes, in the order that they run in.
+    pub const ALL: [Pass; 4] = [
+        Pass::Registers,
+        Pass::Comments,
+        Pass::Aliases,
+        Pass::EmptyLines,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Registers => "registers",
+            Self::Comments => "comments",
+            Self::Aliases => "aliases",
+            Self::EmptyLines => "empty lines",
+        }
+    }
+
+    /// Whether the pass is a cleanup pass, which is run to a fixpoint with the others.
+    pub fn is_cleanup(self) -> bool {
+        matches!(self, Self::EmptyLines)
+    }
+
+    /// Whether a config enables the pass.
+    pub fn is_enabled(self, conf: &OptimizationConfig) -> bool {
+        match self {
+            Self::Registers => conf.optimize_registers,
+            Self::Comments => conf.remove_comments,
+            Self::Aliases => {
+                conf.remove_defines
+                    || conf.remove_reg_aliases
+                    || conf.remove_dev_aliases
+                    || conf.remove_tags
+            }
+            Self::EmptyLines => conf.remove_empty,
+        }
+    }
+
+    fn run(self, mips: &mut Mips, conf: &OptimizationConfig, context: &Context) -> MipsResult<()> {
+        match self {
+            Self::Registers => {
+                allocate_registers(mips);
+                Ok(())
+            }
+            Self::Comments => {
+                for line in mips.lines.iter_mut() {
+                    line.comment_opt = None;
+                }
+                Ok(())
+            }
+            Self::Aliases => replace_aliases(mips, conf, context),
+            Self::EmptyLines => {
+                remove_empty_lines(mips, conf);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Statistics of a pass, over all of its runs.
+#[derive(Clone, Debug)]
+pub struct PassStats {
+    pub name: &'static str,
+    pub runs: usize,
+    pub chars_saved: isize,
+    pub lines_removed: isize,
+    pub registers_saved: isize,
+}
+
+impl PassStats {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            runs: 0,
+            chars_saved: 0,
+            lines_removed: 0,
+            registers_saved: 0,
+        }
+    }
+}
+
+impl Display for PassStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} chars, {} lines, {} registers saved",
+            self.name, self.chars_saved, self.lines_removed, self.registers_saved
+        )
+    }
+}
+
+/// Statistics of an optimization run.
+#[derive(Clone, Debug, Default)]
+pub struct OptimizationStats {
+    /// The statistics of each pass that ran, in order
+    pub passes: Vec<PassStats>,
+    /// How many times the cleanup passes ran
+    pub cleanup_iterations: usize,
+}
+
+impl OptimizationStats {
+    pub fn total_chars_saved(&self) -> isize {
+        self.passes.iter().map(|pass| pass.chars_saved).sum()
+    }
+
+    pub fn total_lines_removed(&self) -> isize {
+        self.passes.iter().map(|pass| pass.lines_removed).sum()
+    }
+
+    pub fn total_registers_saved(&self) -> isize {
+        self.passes.iter().map(|pass| pass.registers_saved).sum()
+    }
+
+    // The statistics of a pass, added if it hasn't run yet
+    fn pass_mut(&mut self, name: &'static str) -> &mut PassStats {
+        match self.passes.iter().position(|pass| pass.name == name) {
+            Some(i) => &mut self.passes[i],
+            None => {
+                self.passes.push(PassStats::new(name));
+                self.passes.last_mut().unwrap()
+            }
+        }
+    }
+}
+
+impl Display for OptimizationStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for pass in self.passes.iter() {
+            writeln!(f, "{}", pass)?;
+        }
+        write!(
+            f,
+            "total: {} chars, {} lines, {} registers saved",
+            self.total_chars_saved(),
+            self.total_lines_removed(),
+            self.total_registers_saved()
+        )
+    }
+}
+
+/// Runs the passes that a config enables over a program.
+#[derive(Clone, Debug)]
+pub struct PassManager {
+    conf: OptimizationConfig,
+    disabled: BTreeSet<Pass>,
+    fixpoint: bool,
+    verify: bool,
+}
+
+impl PassManager {
+    /// A manager running the cleanup passes to a fixpoint, and verifying the program after every
+    /// pass in debug builds.
+    pub fn new(conf: OptimizationConfig) -> Self {
+        Self {
+            conf,
+            disabled: BTreeSet::new(),
+            fixpoint: true,
+            verify: cfg!(debug_assertions),
+        }
+    }
+
+    /// Don't run a pass, even if the config enables it.
+    pub fn without(mut self, pass: Pass) -> Self {
+        self.disabled.insert(pass);
+        self
+    }
+
+    /// Whether to run the cleanup passes until they don't change the program, or only once.
+    pub fn with_fixpoint(mut self, fixpoint: bool) -> Self {
+        self.fixpoint = fixpoint;
+        self
+    }
+
+    /// Whether to verify the program after every pass.
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    pub fn is_enabled(&self, pass: Pass) -> bool {
+        !self.disabled.contains(&pass) && pass.is_enabled(&self.conf)
+    }
+
+    /// Run the passes over a program, returning all errors of lexing or validating it.
+    pub fn run(&self, mips: &Mips) -> Result<(Mips, OptimizationStats), Vec<MipsError>> {
+        let mut mips = mips.clone();
+        let context = Context::new(&mips, &self.conf);
+        mips.lex()?;
+        // The passes (and lifetime analysis) rely on the program being valid to begin with
+        let violations = mips.violations(false);
+        if !violations.is_empty() {
+            return Err(violations);
+        }
+
+        let mut stats = OptimizationStats::default();
+        let (cleanups, passes) = Pass::ALL
+            .iter()
+            .copied()
+            .filter(|pass| self.is_enabled(*pass))
+            .partition::<Vec<_>, _>(|pass| pass.is_cleanup());
+        // The size of the program as the last pass left it
+        let mut size = Size::of(&mips);
+        for pass in passes {
+            self.run_pass(pass, &mut mips, &context, &mut size, &mut stats)?;
+        }
+        // Each iteration but the last removes a line, so this terminates
+        while !cleanups.is_empty() {
+            let mut changed = false;
+            for pass in cleanups.iter() {
+                changed |= self.run_pass(*pass, &mut mips, &context, &mut size, &mut stats)?;
+            }
+            stats.cleanup_iterations += 1;
+            if !changed || !self.fixpoint {
+                break;
+            }
+        }
+
+        // Numbers are written in their shortest form when emitting
+        if self.conf.minimize_size {
+            let numbers = stats.pass_mut("numbers");
+            numbers.runs += 1;
+            numbers.chars_saved = size.text.len() as isize - mips.emit_min().len() as isize;
+        }
+        Ok((mips, stats))
+    }
+
+    // Run a pass, recording its statistics, and return whether it changed the program.
+    //
+    // The size of the program before the pass is the one measured after the previous pass, and is
+    // updated to the size after this one.
+    fn run_pass(
+        &self,
+        pass: Pass,
+        mips: &mut Mips,
+        context: &Context,
+        size: &mut Size,
+        stats: &mut OptimizationStats,
+    ) -> Result<bool, Vec<MipsError>> {
+        pass.run(mips, &self.conf, context).map_err(|e| vec![e])?;
+        if self.verify {
+            let violations = mips.violations(false);
+            if !violations.is_empty() {
+                let invalid = |e| MipsError::pass_invalid(pass.name(), e);
+                return Err(violations.into_iter().map(invalid).collect());
+            }
+        }
+        let before = std::mem::replace(size, Size::of(mips));
+
+        let pass_stats = stats.pass_mut(pass.name());
+        pass_stats.runs += 1;
+        pass_stats.chars_saved += before.text.len() as isize - size.text.len() as isize;
+        pass_stats.lines_removed += before.lines as isize - size.lines as isize;
+        pass_stats.registers_saved += before.registers as isize - size.registers as isize;
+        Ok(before.text != size.text)
+    }
+}
+
+// The size of a program, as the statistics of the passes measure it
+struct Size {
+    text: String,
+    lines: usize,
+    registers: usize,
+}
+
+impl Size {
+    fn of(mips: &Mips) -> Self {
+        Self {
+            text: mips.emit(),
+            lines: mips.lines.len(),
+            registers: mips.num_registers(),
+        }
+    }
+}
+
+// What the passes need to know of the program as it was before any of them ran
+struct Context {
+    // The line of each tag
+    tag_lines: BTreeMap<String, usize>,
+    // Defines to keep rather than inline, since inlining them would lengthen the output
+    kept_defines: BTreeSet<String>,
+}
+
+impl Context {
+    fn new(mips: &Mips, conf: &OptimizationConfig) -> Self {
+        let tag_lines = mips
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| match &line.stmt {
+                Stmt::Tag([key]) => Some((key.to_string(), i)),
+                _ => None,
+            })
+            .collect();
+        let kept_defines = mips
+            .lines
+            .iter()
+            .filter_map(|line| match &line.stmt {
+                Stmt::Define([Arg::String(key), Arg::Num(Num::Lit(n))])
+                    if conf.remove_defines
+                        && conf.minimize_size
+                        && !define_inlining_saves(mips, key, *n) =>
+                {
+                    Some(key.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        Self {
+            tag_lines,
+            kept_defines,
+        }
+    }
+}
+
+/// Whether inlining a define at each of its uses would be shorter than keeping it.
+///
+/// Keeping costs the `define` line and the name at each use, inlining costs the (shortest)
+/// number at each use. Ties are inlined, since that also frees a line.
+fn define_inlining_saves(mips: &Mips, key: &str, n: f64) -> bool {
+    let uses = mips
+        .lines
+        .iter()
+        .flat_map(|line| line.stmt.iter_args())
+        .filter(|arg| matches!(arg, Arg::Num(Num::Alias(k)) if k == key))
+        .count();
+    let num_len = ast_common::shortest_num(n).len();
+    let keep_len = "define ".len() + key.len() + 1 + num_len + 1 + uses * key.len();
+    uses * num_len <= keep_len
+}
+
+fn allocate_registers(mips: &mut Mips) {
+    let lifetimes = mips.analyze_lifetimes();
+    let n = lifetimes.len();
+    let node_iter = lifetimes
+        .iter()
+        .map(|(index, _)| *index)
+        .collect::<BTreeSet<usize>>()
+        .into_iter()
+        .map(|index| (index, index));
+    let edge_iter = (0..n)
+        .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+        .filter_map(|(i, j)| {
+            let (i_index, (i_s, i_e)) = &lifetimes[i];
+            let (j_index, (j_s, j_e)) = &lifetimes[j];
+            ((i_s < j_e) && (j_s < i_e)).then_some((*i_index, *j_index))
+        });
+    let graph = Graph::from_edges(node_iter.chain(edge_iter)).color();
+    let colors = graph
+        .into_nodes()
+        .map(|node| {
+            let index = node.index();
+            let color = node.color().unwrap();
+            (index, color)
+        })
+        .collect::<BTreeMap<_, _>>();
+    for line in mips.lines.iter_mut() {
+        for arg in line.stmt.iter_args_mut() {
+            if let Arg::Dev(Dev::Base(DevBase::Lit(dev_lit))) = arg {
+                if dev_lit.indirections > 0 {
+                    if let Some(new_index) = colors.get(&dev_lit.index) {
+                        dev_lit.index = *new_index;
+                    }
+                }
+            } else if let Some(RegBase::Lit(reg_lit)) = arg.as_reg_base_mut() {
+                if let Some(new_index) = colors.get(&reg_lit.index) {
+                    reg_lit.index = *new_index;
+                }
+            }
+        }
+    }
+}
+
+fn replace_aliases(
+    mips: &mut Mips,
+    conf: &OptimizationConfig,
+    context: &Context,
+) -> MipsResult<()> {
+    let Context {
+        tag_lines,
+        kept_defines,
+    } = context;
+
+    // Empty the lines of defines, aliases and tags
+    for line in mips.lines.iter_mut() {
+        match line {
+            Line {
+                stmt: Stmt::Define([Arg::String(key), ..]),
+                ..
+            } if conf.remove_defines && !kept_defines.contains(key) => {
+                line.stmt = Stmt::Empty([]);
+            }
+            Line {
+                stmt: Stmt::Alias([_, rhs], ..),
+                ..
+            } if matches!(rhs, Arg::Dev(..)) && conf.remove_dev_aliases
+                || matches!(rhs, Arg::Reg(..)) && conf.remove_reg_aliases =>
+            {
+                line.stmt = Stmt::Empty([]);
+            }
+            Line {
+                stmt: Stmt::Tag(_), ..
+            } if conf.remove_tags => {
+                line.stmt = Stmt::Empty([]);
+            }
+            Line { .. } => {}
+        }
+    }
+
+    // Replace their uses
+    let aliases = &mips.aliases;
+    for (i, line) in mips.lines.iter_mut().enumerate() {
+        let result = line.stmt.iter_args_mut().try_for_each(|arg| {
+            match arg {
+                Arg::LineAbs(LineAbs(num)) if conf.remove_tags => {
+                    if let Some(key) = num.as_alias() {
+                        let i = match tag_lines.get(key) {
+                            Some(i) => i,
+                            None => {
+                                let alias = aliases.try_get(key)?;
+                                return Err(MipsError::alias_wrong_kind("a tag", alias));
+                            }
+                        };
+                        *num = Num::Lit(*i as f64);
+                    }
+                }
+                Arg::Num(num) => {
+                    if let Some(key) = num.as_alias() {
+                        if let Some(alias) = aliases.get(key) {
+                            match alias {
+                                Alias::Num(n) => {
+                                    if conf.remove_defines && !kept_defines.contains(key) {
+                                        *arg = Arg::Num(Num::Lit(*n));
+                                    }
+                                }
+                                Alias::Reg(reg_base) => {
+                                    if conf.remove_reg_aliases {
+                                        *arg = Arg::Reg(Reg::Base(*reg_base));
+                                    }
+                                }
+                                Alias::Dev(_) => {
+                                    let expected = "a number or register";
+                                    return Err(MipsError::alias_wrong_kind(expected, alias));
+                                }
+                            }
+                        }
+                    }
+                }
+                Arg::Reg(Reg::Alias { key, .. }) => {
+                    if conf.remove_reg_aliases {
+                        let reg_base = aliases.try_get_reg_base(key)?;
+                        *arg = Arg::Reg(Reg::Base(reg_base));
+                    }
+                }
+                Arg::Dev(Dev::Alias(key)) => {
+                    if conf.remove_dev_aliases {
+                        let dev_base = aliases.try_get_dev_base(key)?;
+                        *arg = Arg::Dev(Dev::Base(dev_base));
+                    }
+                }
+                // Replace named modes with their integer values
+                Arg::BatchMode(..) | Arg::ReagentMode(..) if conf.remove_defines => {
+                    *arg = arg.clone().reduce(aliases)?;
+                }
+                _ => {}
+            }
+            Ok(())
+        });
+        result.map_err(|e| e.on_line(i + 1, &line.to_string()))?;
+    }
+    Ok(())
+}
+
+fn remove_empty_lines(mips: &mut Mips, conf: &OptimizationConfig) {
+    let mut i = 0;
+    while i < mips.lines.len() {
+        let Line {
+            stmt, comment_opt, ..
+        } = &mips.lines[i];
+        match stmt {
+            Stmt::Empty(..) if comment_opt.is_none() || conf.remove_empty_comments => {
+                mips.remove_line(i);
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Jumps to tags, whose lines the aliases pass empties for the empty lines pass to remove
+    const JUMPS: &str = "move r0 1\nj l1\nj l2\nl1:\nl2:\ns d0 On r0\n";
+
+    fn lex(source: &str) -> Mips {
+        Mips::default_with_lines(Mips::parse(source).unwrap().lines).unwrap()
+    }
+
+    fn pass_names(stats: &OptimizationStats) -> Vec<&str> {
+        stats.passes.iter().map(|pass| pass.name).collect()
+    }
+
+    // The path of a test config
+    fn config(file: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-scripts/config")
+            .join(file)
+    }
+
+    // The names of the switches of a config which are on
+    fn switches_on(mut conf: OptimizationConfig) -> Vec<&'static str> {
+        let fields = OptimizationConfig::FIELDS.iter().copied();
+        fields
+            .filter(|name| *conf.field_mut(name).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn config_from_file() {
+        // The format is picked by the extension, and missing fields are defaults
+        for file in ["keep.toml", "keep.ron"] {
+            let conf = OptimizationConfig::from_file(config(file)).unwrap();
+            let expected = OptimizationConfig {
+                remove_comments: false,
+                remove_tags: false,
+                ..OptimizationConfig::default()
+            };
+            assert_eq!(switches_on(conf), switches_on(expected), "{}", file);
+        }
+        let message = OptimizationConfig::from_file(config("typo.toml"))
+            .unwrap_err()
+            .to_string();
+        assert!(message.starts_with("Config "), "{}", message);
+        assert!(message.contains("`remove_coments`"), "{}", message);
+    }
+
+    #[test]
+    fn config_presets() {
+        let size = OptimizationConfig::preset("size").unwrap();
+        assert_eq!(switches_on(size), OptimizationConfig::FIELDS);
+        let readable = OptimizationConfig::preset("readable").unwrap();
+        assert_eq!(switches_on(readable), ["optimize_registers"]);
+        let debug = OptimizationConfig::preset("debug").unwrap();
+        assert!(switches_on(debug).is_empty());
+        let message = OptimizationConfig::preset("fast").unwrap_err().to_string();
+        let expected = "Preset 'fast' unknown, expected one of size, readable, debug";
+        assert_eq!(message, expected);
+    }
+
+    #[test]
+    fn fixpoint_terminates() {
+        let mips = lex(JUMPS);
+        let manager = PassManager::new(OptimizationConfig::default());
+        let (output, stats) = manager.run(&mips).unwrap();
+        assert_eq!(output.emit(), "move r0 1\nj 3\nj 3\ns d0 On r0\n");
+        // The last iteration finds nothing left to change
+        assert_eq!(stats.cleanup_iterations, 2);
+        for pass in stats.passes.iter() {
+            let runs = if pass.name == "empty lines" {
+                stats.cleanup_iterations
+            } else {
+                1
+            };
+            assert_eq!(pass.runs, runs, "{}", pass.name);
+        }
+
+        // An optimized program is a fixpoint itself
+        let (again, stats) = manager.run(&output).unwrap();
+        assert_eq!(again.emit(), output.emit());
+        assert_eq!(stats.cleanup_iterations, 1);
+        assert_eq!(stats.total_chars_saved(), 0);
+        assert_eq!(stats.total_lines_removed(), 0);
+    }
+
+    #[test]
+    fn without_fixpoint() {
+        let manager = PassManager::new(OptimizationConfig::default()).with_fixpoint(false);
+        let (output, stats) = manager.run(&lex(JUMPS)).unwrap();
+        assert_eq!(output.emit(), "move r0 1\nj 3\nj 3\ns d0 On r0\n");
+        assert_eq!(stats.cleanup_iterations, 1);
+    }
+
+    #[test]
+    fn stats_totals() {
+        let mips = lex(JUMPS);
+        let (output, stats) = PassManager::new(OptimizationConfig::default())
+            .run(&mips)
+            .unwrap();
+        let names = ["registers", "comments", "aliases", "empty lines"];
+        assert_eq!(pass_names(&stats), names);
+        let chars_saved = mips.emit().len() - output.emit().len();
+        assert_eq!(stats.total_chars_saved(), chars_saved as isize);
+        assert_eq!(stats.total_lines_removed(), 2);
+        let empty_lines = &stats.passes[3];
+        assert_eq!(empty_lines.lines_removed, 2);
+        assert_eq!(empty_lines.chars_saved, 2);
+    }
+
+    #[test]
+    fn stats_without_pass() {
+        let manager = PassManager::new(OptimizationConfig::default()).without(Pass::Aliases);
+        let (output, stats) = manager.run(&lex(JUMPS)).unwrap();
+        let names = ["registers", "comments", "empty lines"];
+        assert_eq!(pass_names(&stats), names);
+        // The tags are kept, so their lines aren't empty
+        assert_eq!(output.lines.len(), 6);
+        assert_eq!(stats.total_lines_removed(), 0);
+    }
+
+    #[test]
+    fn all_errors() {
+        let manager = PassManager::new(OptimizationConfig::default());
+        // Every line that fails to lex
+        let mips = Mips::parse("move r0 foo\nj 1\ns d0 Setting bar\n").unwrap();
+        let errors = manager.run(&mips).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        // Every violation
+        let mips = lex("j 5\nmove r0 1\njr -4\n");
+        let errors = manager.run(&mips).unwrap_err();
+        let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("Jump target 5"), "{}", messages[0]);
+        assert!(messages[1].contains("Jump target -2"), "{}", messages[1]);
+    }
+}
@@ -0,0 +1,223 @@
+//! Checking the invariants of a program.
+use std::collections::BTreeMap;
+
+use ast_traits::SourceSpan;
+
+use crate::ast::{Arg, Dev, DevBase, DevLit, FixMode, LineAbs, LineRel, MipsNode, Num, Reg};
+use crate::ast::{RegBase, Stmt};
+use crate::{Alias, Aliases, Mips, MipsError};
+
+/// The highest register index (`r16` and `r17` being `sp` and `ra`).
+pub const REG_MAX: usize = 15;
+
+/// The highest device index.
+pub const DEV_MAX: usize = 5;
+
+// The use of an alias: its key, the kind it should be, and a check of that kind
+type AliasUse<'a> = (&'a String, &'static str, fn(&Alias) -> bool);
+
+impl Mips {
+    /// Check the invariants of the program, returning all violations:
+    /// * jump targets are within the program (or just past its end)
+    /// * aliases and defines are defined before they're used, tags anywhere, and each as the kind
+    ///   its uses expect
+    /// * registers are below `r16` and devices below `d6`
+    /// * register scopes ([`FixMode::Scoped`]) are in order and within the program (or end just past
+    ///   its end)
+    pub fn verify(&self) -> Result<(), Vec<MipsError>> {
+        let errors = self.violations(true);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // The violations of the invariants of the program.
+    //
+    // Unless strict, aliases and defines may be used before they're defined, and registers and
+    // devices aren't checked, so that programs whose registers aren't allocated yet can be checked
+    // (e.g. between the passes of the optimizer).
+    pub(crate) fn violations(&self, strict: bool) -> Vec<MipsError> {
+        let n = self.lines.len();
+        let builtins = Aliases::default();
+        let mut defined = BTreeMap::new();
+        // Tags can be used before them, by jumping forward
+        for (i, line) in self.lines.iter().enumerate() {
+            if let Stmt::Tag([Arg::String(key)]) = &line.stmt {
+                defined.insert(key.as_str(), Alias::Num(i as f64));
+            }
+        }
+        if !strict {
+            for line in self.lines.iter() {
+                define(&mut defined, &line.stmt);
+            }
+        }
+
+        let mut errors = Vec::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            let text = line.to_string();
+            let mut push = |e: MipsError| errors.push(e.on_line(i + 1, &text));
+            for arg in line.stmt.iter_args() {
+                // Aliases are defined and of the right kind
+                let use_opt: Option<AliasUse> = match arg {
+                    Arg::Reg(Reg::Alias { key, .. }) => {
+                        Some((key, "a register", |alias| matches!(alias, Alias::Reg(..))))
+                    }
+                    Arg::Dev(Dev::Alias(key)) => {
+                        Some((key, "a device", |alias| matches!(alias, Alias::Dev(..))))
+                    }
+                    Arg::Num(Num::Alias(key))
+                    | Arg::LineAbs(LineAbs(Num::Alias(key)))
+                    | Arg::LineRel(LineRel(Num::Alias(key))) => {
+                        Some((key, "a number or register", |alias| {
+                            !matches!(alias, Alias::Dev(..))
+                        }))
+                    }
+                    _ => None,
+                };
+                if let Some((key, expected, is_kind)) = use_opt {
+                    match defined.get(key.as_str()).or_else(|| builtins.get(key)) {
+                        Some(alias) => {
+                            if !is_kind(alias) {
+                                push(MipsError::alias_wrong_kind(expected, alias));
+                            }
+                        }
+                        None => {
+                            let span = SourceSpan::token(i + 1, &text, key);
+                            push(MipsError::alias_undefined(key).at(span));
+                        }
+                    }
+                }
+
+                // Jumps are within the program
+                let target_opt = match arg {
+                    Arg::LineAbs(LineAbs(Num::Lit(target))) => Some(*target),
+                    Arg::LineRel(LineRel(Num::Lit(offset))) => Some(i as f64 + offset),
+                    _ => None,
+                };
+                if let Some(target) = target_opt {
+                    if target < 0_f64 || target > n as f64 {
+                        push(MipsError::target_invalid(target));
+                    }
+                }
+
+                // Registers and devices exist, and scopes are within the program
+                if let Some(RegBase::Lit(reg_lit)) = arg.as_reg_base() {
+                    if strict && reg_lit.index > REG_MAX {
+                        push(MipsError::reg_invalid(reg_lit.index));
+                    }
+                    if let FixMode::Scoped(s, e) = reg_lit.fix_mode {
+                        if s > e || e > n {
+                            push(MipsError::scope_invalid(s, e));
+                        }
+                    }
+                }
+                if let Arg::Dev(Dev::Base(DevBase::Lit(DevLit {
+                    index,
+                    indirections: 0,
+                }))) = arg
+                {
+                    if strict && *index > DEV_MAX {
+                        push(MipsError::dev_invalid(*index));
+                    }
+                }
+            }
+            define(&mut defined, &line.stmt);
+        }
+        errors
+    }
+}
+
+// Define the alias or define of a statement, if it's one
+fn define<'a>(defined: &mut BTreeMap<&'a str, Alias>, stmt: &'a Stmt) {
+    let (key, alias_opt) = match stmt {
+        Stmt::Define([Arg::String(key), Arg::Num(Num::Lit(n))]) => (key, Some(Alias::Num(*n))),
+        Stmt::Alias([Arg::String(key), Arg::Reg(Reg::Base(reg_base))]) => {
+            (key, Some(Alias::Reg(*reg_base)))
+        }
+        Stmt::Alias([Arg::String(key), Arg::Dev(Dev::Base(dev_base))]) => {
+            (key, Some(Alias::Dev(*dev_base)))
+        }
+        // An alias of an alias has the kind of the other alias
+        Stmt::Alias([Arg::String(key), Arg::Reg(Reg::Alias { key: other, .. })])
+        | Stmt::Alias([Arg::String(key), Arg::Dev(Dev::Alias(other))]) => {
+            (key, defined.get(other.as_str()).cloned())
+        }
+        _ => return,
+    };
+    if let Some(alias) = alias_opt {
+        defined.insert(key.as_str(), alias);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A program violating each invariant once, with an invalid scope on its first line
+    fn program() -> Mips {
+        let source = "\
+move r0 1
+j 9
+move r16 r0
+s d6 On 1
+j nowhere
+s sensor On 1
+add r1 r0 half
+define half 0.5
+";
+        let mut mips = Mips::parse(source).unwrap();
+        // Scopes can't be written in a source, they're only set when translating
+        let arg = mips.lines[0].stmt.iter_args_mut().next().unwrap();
+        arg.as_reg_lit_mut().unwrap().fix_mode = FixMode::Scoped(3, 1);
+        mips
+    }
+
+    // Assert the line and (first line of the) message of each violation
+    fn assert_violations(mips: &Mips, strict: bool, expected: &[(usize, &str)]) {
+        let errors = mips.violations(strict);
+        let messages = errors
+            .iter()
+            .map(|e| (e.span().unwrap().line, e.to_string()))
+            .collect::<Vec<_>>();
+        let found = messages
+            .iter()
+            .map(|(line, message)| (*line, message.lines().next().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn all_violations() {
+        let expected = [
+            (1, "Register scope (3,1) is invalid"),
+            (2, "Jump target 9 is outside of the program"),
+            (3, "Register r16 doesn't exist (the last is r15)"),
+            (4, "Device d6 doesn't exist (the last is d5)"),
+            (5, "Alias nowhere undefined"),
+            (6, "Alias sensor undefined"),
+            (7, "Alias half undefined"),
+        ];
+        assert_violations(&program(), true, &expected);
+        assert_eq!(program().verify().unwrap_err().len(), expected.len());
+    }
+
+    #[test]
+    fn non_strict_violations() {
+        // Registers and devices aren't checked, and defines can be used before them
+        let expected = [
+            (1, "Register scope (3,1) is invalid"),
+            (2, "Jump target 9 is outside of the program"),
+            (5, "Alias nowhere undefined"),
+            (6, "Alias sensor undefined"),
+        ];
+        assert_violations(&program(), false, &expected);
+    }
+
+    #[test]
+    fn no_violations() {
+        let mips = Mips::parse("alias x r15\nmove x 1\ns d5 On x\nj 4\n").unwrap();
+        assert!(mips.verify().is_ok());
+    }
+}
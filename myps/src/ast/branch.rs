@@ -26,6 +26,19 @@ pub enum Branch {
     },
     For(Var, Expr, Expr, Expr),
     Tag(String),
+    Fn {
+        name: String,
+        params: Vec<Var>,
+    },
+    Macro {
+        name: String,
+        params: Vec<Var>,
+    },
+    /// The items of an imported file, along with its path and source (for locating errors)
+    Import {
+        path: String,
+        source: String,
+    },
 }
 
 impl Branch {
@@ -41,6 +54,18 @@ impl Branch {
         matches!(self, Self::Else { .. })
     }
 
+    pub fn is_fn(&self) -> bool {
+        matches!(self, Self::Fn { .. })
+    }
+
+    pub fn is_macro(&self) -> bool {
+        matches!(self, Self::Macro { .. })
+    }
+
+    pub fn is_loop(&self) -> bool {
+        matches!(self, Self::Loop | Self::While { .. } | Self::For(..))
+    }
+
     pub fn is_if_elif_else(&self) -> bool {
         match self {
             Self::If { .. } | Self::Elif { .. } | Self::Else { .. } => true,
@@ -56,21 +81,21 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Branch {
 
     fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
         match pair.as_rule() {
-            Rule::branch => pair.only_inner().unwrap().try_into_ast(),
+            Rule::branch => pair.only_inner()?.try_into_ast(),
             Rule::branch_loop => Ok(Self::Loop),
             Rule::branch_while => {
-                let cond = pair.only_inner().unwrap().try_into_ast().unwrap();
+                let cond = pair.only_inner()?.try_into_ast()?;
                 Ok(Self::While { cond })
             }
             Rule::branch_if => {
-                let cond = pair.only_inner().unwrap().try_into_ast().unwrap();
+                let cond = pair.only_inner()?.try_into_ast()?;
                 Ok(Self::If {
                     cond,
                     chain_id_opt: None,
                 })
             }
             Rule::branch_elif => {
-                let cond = pair.only_inner().unwrap().try_into_ast().unwrap();
+                let cond = pair.only_inner()?.try_into_ast()?;
                 Ok(Self::Elif {
                     cond,
                     chain_id: 0, // NOTE: Updated in lexer
@@ -84,21 +109,31 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Branch {
             }
             Rule::branch_for => {
                 let mut pairs = pair.into_inner();
-                let i = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let s = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let e = pairs.next_pair().unwrap().try_into_ast().unwrap();
+                let i = pairs.next_pair()?.try_into_ast()?;
+                let s = pairs.next_pair()?.try_into_ast()?;
+                let e = pairs.next_pair()?.try_into_ast()?;
                 let step = pairs
                     .next()
                     .map(Expr::try_from_pair)
-                    .transpose()
-                    .unwrap()
+                    .transpose()?
                     .unwrap_or(1.into());
                 Ok(Self::For(i, s, e, step))
             }
             Rule::branch_tag => {
-                let tag = pair.only_inner().unwrap().try_into_ast().unwrap();
+                let tag = pair.only_inner()?.try_into_ast()?;
                 Ok(Self::Tag(tag))
             }
+            Rule::branch_fn | Rule::branch_macro => {
+                let is_fn = matches!(pair.as_rule(), Rule::branch_fn);
+                let mut pairs = pair.into_inner();
+                let name = pairs.next_pair()?.try_into_ast()?;
+                let params = pairs.map(Var::try_from_pair).collect::<MypsResult<_>>()?;
+                if is_fn {
+                    Ok(Self::Fn { name, params })
+                } else {
+                    Ok(Self::Macro { name, params })
+                }
+            }
             _ => Err(MypsError::pair_wrong_rule("a branch", pair)),
         }
     }
@@ -0,0 +1,87 @@
+use ast_common::constants::ConstError;
+use ast_common::{BatchMode, LogicType, ReagentMode, SlotLogicType};
+use ast_traits::{AstError, AstNode, AstPair, IntoAst, SourceSpan};
+
+use crate::ast::Num;
+use crate::{MypsError, MypsParser, MypsResult, Pair, Rule};
+
+/// A batch or reagent mode: either literal (a name or an integer), or a number known at runtime.
+#[derive(Clone, Debug)]
+pub enum Mode<M> {
+    Lit(M),
+    Num(Box<Num>),
+}
+
+// Locate an unknown name at its pair
+fn const_error(e: ConstError, pair: &Pair) -> MypsError {
+    MypsError::from(e).at(SourceSpan::from_pest(pair.as_span()))
+}
+
+impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for LogicType {
+    type Output = Self;
+
+    const RULE: Rule = Rule::token;
+
+    fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
+        match pair.as_rule() {
+            Rule::token => LogicType::new(pair.as_str()).map_err(|e| const_error(e, &pair)),
+            _ => Err(MypsError::pair_wrong_rule("a logic type", pair)),
+        }
+    }
+}
+
+impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for SlotLogicType {
+    type Output = Self;
+
+    const RULE: Rule = Rule::token;
+
+    fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
+        match pair.as_rule() {
+            Rule::token => SlotLogicType::new(pair.as_str()).map_err(|e| const_error(e, &pair)),
+            _ => Err(MypsError::pair_wrong_rule("a slot logic type", pair)),
+        }
+    }
+}
+
+// A mode from the number-like of its pair: literal if it's a name or an integer of the mode, and
+// otherwise the number-like (e.g. a variable), whose value is the mode at runtime.
+//
+// An integer which isn't a mode is an error (rather than a number which can't be one).
+fn mode_from_pair<M>(
+    pair: Pair,
+    from_token: fn(&str) -> Result<M, ConstError>,
+) -> MypsResult<Mode<M>> {
+    let num_pair = pair.only_inner()?;
+    let s = num_pair.as_str();
+    match from_token(s) {
+        Ok(mode) => Ok(Mode::Lit(mode)),
+        Err(e) if s.parse::<i64>().is_ok() => Err(const_error(e, &num_pair)),
+        Err(_) => Ok(Mode::Num(Box::new(num_pair.try_into_ast()?))),
+    }
+}
+
+impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Mode<BatchMode> {
+    type Output = Self;
+
+    const RULE: Rule = Rule::batch_mode;
+
+    fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
+        match pair.as_rule() {
+            Rule::batch_mode => mode_from_pair(pair, BatchMode::from_token),
+            _ => Err(MypsError::pair_wrong_rule("a batch mode", pair)),
+        }
+    }
+}
+
+impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Mode<ReagentMode> {
+    type Output = Self;
+
+    const RULE: Rule = Rule::reagent_mode;
+
+    fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
+        match pair.as_rule() {
+            Rule::reagent_mode => mode_from_pair(pair, ReagentMode::from_token),
+            _ => Err(MypsError::pair_wrong_rule("a reagent mode", pair)),
+        }
+    }
+}
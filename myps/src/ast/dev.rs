@@ -19,22 +19,20 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Dev {
 
     fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
         match pair.as_rule() {
-            Rule::dev_var | Rule::dev => pair.only_inner().unwrap().try_into_ast(),
+            Rule::dev_var | Rule::dev => pair.only_inner()?.try_into_ast(),
             Rule::dev_self => Ok(Self::DB),
             Rule::dev_lit => {
                 let index = pair
-                    .only_rule(Rule::int, "an index")
-                    .unwrap()
+                    .only_rule(Rule::int, "an index")?
                     .as_str()
-                    .parse()
-                    .unwrap();
+                    .parse()?;
                 Ok(Self::Lit(index))
             }
             Rule::dev_expr => {
-                let expr = pair.only_inner().unwrap().try_into_ast().unwrap();
+                let expr = pair.only_inner()?.try_into_ast()?;
                 Ok(Self::Expr(Box::new(expr)))
             }
-            Rule::var => Ok(Self::Var(pair.try_into_ast().unwrap())),
+            Rule::var => Ok(Self::Var(pair.try_into_ast()?)),
             _ => return Err(MypsError::pair_wrong_rule("a device", pair)),
         }
     }
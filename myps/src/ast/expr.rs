@@ -9,7 +9,18 @@ use crate::{MypsError, MypsParser, MypsResult, Pair, Pairs, Rule};
 
 #[rustfmt::skip]
 #[derive(Clone, Debug)]
-pub enum UnaryOp { Inv, Not, }
+pub enum UnaryOp { Inv, Not, BitNot, }
+
+impl UnaryOp {
+    /// Apply the operator to a number.
+    pub fn apply(&self, n: f64) -> f64 {
+        match self {
+            Self::Inv => -n,
+            Self::Not => bool_to_float(n == 0_f64),
+            Self::BitNot => wrap_int(!to_int(n)) as f64,
+        }
+    }
+}
 
 impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for UnaryOp {
     type Output = Self;
@@ -20,6 +31,7 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for UnaryOp {
         match pair.as_rule() {
             Rule::op_u_inv => Ok(Self::Inv),
             Rule::op_u_not => Ok(Self::Not),
+            Rule::op_u_bit_not => Ok(Self::BitNot),
             _ => Err(MypsError::pair_wrong_rule("a unary operator", pair)),
         }
     }
@@ -32,10 +44,44 @@ pub enum BinaryOp {
     Add, Sub, Mul, Div, Rem, Pow,
     // Logical
     And, Nor, Or, Xor,
+    // Bitwise
+    Sll, Srl, Sra,
     // Relational
     Eq, Ge, Gt, Le, Lt, Ne,
 }
 
+impl BinaryOp {
+    /// Apply the operator to numbers.
+    #[rustfmt::skip]
+    pub fn apply(&self, l: f64, r: f64) -> f64 {
+        match self {
+            // Numerical
+            Self::Add => l + r,
+            Self::Sub => l - r,
+            Self::Mul => l * r,
+            Self::Div => l / r,
+            Self::Rem => l % r,
+            Self::Pow => l.powf(r),
+            // Logical
+            Self::And => bool_to_float((l != 0_f64) & (r != 0_f64)),
+            Self::Nor => bool_to_float(!((l != 0_f64) | (r != 0_f64))),
+            Self::Or  => bool_to_float((l != 0_f64) | (r != 0_f64)),
+            Self::Xor => bool_to_float((l != 0_f64) ^ (r != 0_f64)),
+            // Bitwise
+            Self::Sll => wrap_int(to_int(l) << shift(r)) as f64,
+            Self::Srl => wrap_int((to_int(l) & INT_MASK) >> shift(r)) as f64,
+            Self::Sra => (to_int(l) >> shift(r)) as f64,
+            // Relational
+            Self::Eq  => bool_to_float(l == r),
+            Self::Ge  => bool_to_float(l >= r),
+            Self::Gt  => bool_to_float(l >  r),
+            Self::Le  => bool_to_float(l <= r),
+            Self::Lt  => bool_to_float(l <  r),
+            Self::Ne  => bool_to_float(l != r),
+        }
+    }
+}
+
 impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for BinaryOp {
     type Output = Self;
 
@@ -55,6 +101,10 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for BinaryOp {
             Rule::op_b_and => Ok(Self::And),
             Rule::op_b_or  => Ok(Self::Or),
             Rule::op_b_xor => Ok(Self::Xor),
+            // Bitwise
+            Rule::op_b_sll => Ok(Self::Sll),
+            Rule::op_b_srl => Ok(Self::Srl),
+            Rule::op_b_sra => Ok(Self::Sra),
             // Relational
             Rule::op_b_eq  => Ok(Self::Eq),
             Rule::op_b_ge  => Ok(Self::Ge),
@@ -83,6 +133,27 @@ fn bool_to_float(b: bool) -> f64 {
     }
 }
 
+// The number of bits of the integers which bitwise operators work on (as many as a float holds
+// exactly)
+const INT_BITS: u32 = 53;
+const INT_MASK: i64 = (1 << INT_BITS) - 1;
+
+// Wrap an integer to its lowest bits, as a two's complement integer
+fn wrap_int(i: i64) -> i64 {
+    let unused = 64 - INT_BITS;
+    (i << unused) >> unused
+}
+
+// A number as an integer (truncated and wrapped) for a bitwise operator
+fn to_int(n: f64) -> i64 {
+    wrap_int(n as i64)
+}
+
+// A number as a shift amount (any past the integer bits shift them all out)
+fn shift(n: f64) -> u32 {
+    (n as i64).clamp(0, 63) as u32
+}
+
 impl Expr {
     pub fn unary(op: UnaryOp, rhs: Expr) -> Self {
         let rhs = rhs.into();
@@ -107,13 +178,7 @@ impl Expr {
             Self::Unary { op, rhs } => {
                 let rhs = rhs.simplify();
                 match rhs {
-                    Num::Lit(n) => {
-                        let n = match op {
-                            UnaryOp::Inv => -n,
-                            UnaryOp::Not => bool_to_float(n == 0_f64),
-                        };
-                        n.into()
-                    }
+                    Num::Lit(n) => op.apply(n).into(),
                     rhs => Self::Unary { op, rhs },
                 }
             }
@@ -121,30 +186,7 @@ impl Expr {
                 let lhs = lhs.simplify();
                 let rhs = rhs.simplify();
                 match (lhs, rhs) {
-                    (Num::Lit(l), Num::Lit(r)) => {
-                        #[rustfmt::skip]
-                        let n = match op {
-                            BinaryOp::Add => l + r,
-                            BinaryOp::Sub => l - r,
-                            BinaryOp::Mul => l * r,
-                            BinaryOp::Div => l / r,
-                            BinaryOp::Rem => l % r,
-                            BinaryOp::Pow => l.powf(r),
-                            // Logical
-                            BinaryOp::And => bool_to_float((l != 0_f64) & (r != 0_f64)),
-                            BinaryOp::Nor => unimplemented!(),
-                            BinaryOp::Or  => bool_to_float((l != 0_f64) | (r != 0_f64)),
-                            BinaryOp::Xor => bool_to_float((l != 0_f64) ^ (r != 0_f64)),
-                            // Relational
-                            BinaryOp::Eq  => bool_to_float(l == r),
-                            BinaryOp::Ge  => bool_to_float(l >= r),
-                            BinaryOp::Gt  => bool_to_float(l >  r),
-                            BinaryOp::Le  => bool_to_float(l <= r),
-                            BinaryOp::Lt  => bool_to_float(l <  r),
-                            BinaryOp::Ne  => bool_to_float(l != r),
-                        };
-                        n.into()
-                    }
+                    (Num::Lit(l), Num::Lit(r)) => op.apply(l, r).into(),
                     (lhs, rhs) => Self::Binary { op, lhs, rhs },
                 }
             }
@@ -183,22 +225,22 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Expr {
 
     fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
         let expr = match pair.as_rule() {
-            Rule::expr => pair.only_inner().unwrap().try_into_ast().unwrap(),
+            Rule::expr => pair.only_inner()?.try_into_ast()?,
             Rule::expr_unary => {
                 let mut pairs = pair.into_inner();
-                let op = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let rhs = pairs.final_pair().unwrap().try_into_ast().unwrap();
+                let op = pairs.next_pair()?.try_into_ast()?;
+                let rhs = pairs.final_pair()?.try_into_ast()?;
                 Expr::unary(op, rhs)
             }
             Rule::expr_binary => expr_climb(pair.into_inner()),
             Rule::expr_ternary => {
                 let mut pairs = pair.into_inner();
-                let cond = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let if_t = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let if_f = pairs.final_pair().unwrap().try_into_ast().unwrap();
+                let cond = pairs.next_pair()?.try_into_ast()?;
+                let if_t = pairs.next_pair()?.try_into_ast()?;
+                let if_f = pairs.final_pair()?.try_into_ast()?;
                 Expr::ternary(cond, if_t, if_f)
             }
-            Rule::num_var | Rule::num | Rule::var => Self::Num(pair.try_into_ast().unwrap()),
+            Rule::num_var | Rule::num | Rule::var => Self::Num(pair.try_into_ast()?),
             _ => {
                 return Err(MypsError::pair_wrong_rule(
                     "an expression or number-like",
@@ -223,6 +265,10 @@ use Rule::{
     op_b_and as And,
     op_b_or  as Or,
     op_b_xor as Xor,
+    // Bitwise
+    op_b_sll as Sll,
+    op_b_srl as Srl,
+    op_b_sra as Sra,
     // Relational
     op_b_eq  as Eq,
     op_b_ge  as Ge,
@@ -248,6 +294,7 @@ lazy_static! {
         Op!(And, L),
         Op!(Eq,  L) | Op!(Ne,  L),
         Op!(Ge,  L) | Op!(Gt,  L) | Op!(Le,  L) | Op!(Lt,  L),
+        Op!(Sll, L) | Op!(Srl, L) | Op!(Sra, L),
         Op!(Add, L) | Op!(Sub, L),
         Op!(Mul, L) | Op!(Div, L) | Op!(Rem, L),
         Op!(Pow, R),
@@ -263,3 +310,58 @@ fn infix(lhs: Expr, op_pair: Pair, rhs: Expr) -> Expr {
 pub fn expr_climb(pairs: Pairs) -> Expr {
     CLIMBER.climb(pairs, Expr::from_pair, infix)
 }
+
+#[cfg(test)]
+mod tests {
+    use pest::Parser;
+
+    use super::*;
+
+    // Parse and fold a constant expression
+    fn fold(source: &str) -> f64 {
+        let pair = MypsParser::parse(Rule::expr, source)
+            .unwrap()
+            .only_pair()
+            .unwrap();
+        match pair.try_into_ast::<Expr>().unwrap() {
+            Expr::Num(Num::Lit(n)) => n,
+            expr => panic!("{} isn't folded: {:?}", source, expr),
+        }
+    }
+
+    #[test]
+    fn fold_shift_left() {
+        assert_eq!(fold("1 << 4"), 16.0);
+        // The top bit of the 53 is the sign bit, and bits past it are lost
+        assert_eq!(fold("1 << 52"), -(2_f64.powi(52)));
+        assert_eq!(fold("1 << 53"), 0.0);
+        assert_eq!(fold("3 << 52"), -(2_f64.powi(52)));
+        assert_eq!(fold("1 << 100"), 0.0);
+        // Operands are truncated
+        assert_eq!(fold("5.7 << 1"), 10.0);
+    }
+
+    #[test]
+    fn fold_shift_right() {
+        assert_eq!(fold("-8 >> 1"), -4.0);
+        assert_eq!(fold("-1 >> 100"), -1.0);
+        assert_eq!(fold("-1 >>> 1"), 2_f64.powi(52) - 1.0);
+        assert_eq!(fold("-1 >>> 52"), 1.0);
+        assert_eq!(fold("16 >>> 2"), 4.0);
+        assert_eq!(fold("16 >>> -2"), 16.0);
+    }
+
+    #[test]
+    fn fold_bit_not() {
+        assert_eq!(fold("~0"), -1.0);
+        assert_eq!(fold("~5"), -6.0);
+        assert_eq!(fold("~(2 ^ 53 - 1)"), 0.0);
+    }
+
+    #[test]
+    fn shift_precedence() {
+        // Below arithmetic, above relational
+        assert_eq!(fold("1 + 1 << 2"), 8.0);
+        assert_eq!(fold("1 << 2 < 5"), 1.0);
+    }
+}
@@ -2,10 +2,10 @@ use std::convert::TryInto;
 
 use itertools::join;
 
-use ast_traits::{AstError, AstNode, AstPair, AstPairs, IntoAst};
+use ast_traits::{AstError, AstErrorBase, AstNode, AstPair, AstPairs, IntoAst};
 use mips::MipsResult;
 
-use crate::ast::{Dev, Expr, Num};
+use crate::ast::{Dev, Expr, Num, Rv};
 use crate::{MypsError, MypsParser, MypsResult, Pair, Pairs, Rule};
 
 #[derive(Clone, Debug)]
@@ -33,9 +33,9 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Arg {
 
     fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
         match pair.as_rule() {
-            Rule::rv => pair.only_inner().unwrap().try_into_ast(),
-            Rule::expr => Ok(Self::Expr(pair.try_into_ast().unwrap())),
-            Rule::dev => Ok(Self::Dev(pair.try_into_ast().unwrap())),
+            Rule::rv => pair.only_inner()?.try_into_ast(),
+            Rule::expr => Ok(Self::Expr(pair.try_into_ast()?)),
+            Rule::dev => Ok(Self::Dev(pair.try_into_ast()?)),
             // Rule::var => Ok(Self::Var(pair.try_into_ast().unwrap())),
             _ => Err(MypsError::pair_wrong_rule(
                 "an r-value (device or expression)",
@@ -57,6 +57,11 @@ macro_rules! def_func {
             )*
         }
 
+        impl Func {
+            /// The names of the builtin functions.
+            pub const NAMES: &'static [&'static str] = &[$($disp),*];
+        }
+
         impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Func {
             type Output = Self;
 
@@ -64,17 +69,16 @@ macro_rules! def_func {
 
             fn try_from_pair(pair: Pair) -> MypsResult<Self::Output> {
                 let mut pairs = pair.into_inner();
-                let name_str = pairs.next_pair().unwrap().as_str();
+                let name_str = pairs.next_pair()?.as_str();
                 let arg_pairs = pairs
                     .map(|pair| {
                         if matches!(pair.as_rule(), Rule::rv) {
-                            Ok(pair.only_inner().unwrap())
+                            Ok(pair.only_inner()?)
                         } else {
                             Err(MypsError::pair_wrong_rule("an r-value", pair))
                         }
                     })
-                    .collect::<MypsResult<Vec<_>>>()
-                    .unwrap();
+                    .collect::<MypsResult<Vec<_>>>()?;
                 #[allow(dead_code, unused_variables, unused_mut)]
                 match name_str {
                     $(
@@ -92,7 +96,7 @@ macro_rules! def_func {
                             let mut pairs = arg_pairs.into_iter();
                             let args: [Arg; $n_args] = [
                                 $({
-                                    let pair = pairs.next().unwrap();
+                                    let pair = pairs.next().ok_or(AstErrorBase::PairsNotEnough)?;
                                     let ast = <$arg_kind>::try_from_pair(pair)
                                         .map_err(|_| {
                                             MypsError::func_args_wrong_kinds(
@@ -100,8 +104,7 @@ macro_rules! def_func {
                                                 $expected,
                                                 &found,
                                             )
-                                        })
-                                        .unwrap();
+                                        })?;
                                     ast.into()
                                 }),*
                             ];
@@ -149,6 +152,38 @@ def_func!(
     (Pop,   0, "pop",   "null",      [    ]),
 );
 
+/// A call of a function defined by the program (`fn`).
+#[derive(Clone, Debug)]
+pub struct Call {
+    pub name: String,
+    pub args: Vec<Expr>,
+}
+
+impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Call {
+    type Output = Self;
+
+    const RULE: Rule = Rule::num_func;
+
+    fn try_from_pair(pair: Pair) -> MypsResult<Self::Output> {
+        match pair.as_rule() {
+            Rule::num_func | Rule::stmt_mips => {
+                let mut pairs = pair.into_inner();
+                let name = pairs.next_pair()?.as_str().to_owned();
+                let args = pairs
+                    .enumerate()
+                    .map(|(i, pair)| match pair.try_into_ast()? {
+                        Rv::Expr(expr) => Ok(expr),
+                        Rv::Var(var) => Ok(Num::Var(var).into()),
+                        Rv::Dev(..) => Err(MypsError::fn_arg_invalid(&name, i)),
+                    })
+                    .collect::<MypsResult<Vec<Expr>>>()?;
+                Ok(Self { name, args })
+            }
+            _ => Err(MypsError::pair_wrong_rule("a function call", pair)),
+        }
+    }
+}
+
 // impl<'i> IntoMips<'i> for Func {
 //     type Output = (usize, mips::ast::Num, Vec<mips::ast::Stmt>);
 
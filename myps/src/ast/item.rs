@@ -187,35 +187,32 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Item {
         match pair.as_rule() {
             Rule::single_line => {
                 let mut pairs = pair.into_inner();
-                let line_pair = pairs.next_pair().unwrap();
-                pairs.final_pair().unwrap();
+                let line_pair = pairs.next_pair()?;
+                pairs.final_pair()?;
                 Self::try_from_pair(line_pair)
             }
             Rule::line => {
                 let mut pairs = pair.into_inner();
                 let spaces = pairs
-                    .next_rule(Rule::indent, "an indent")
-                    .unwrap()
+                    .next_rule(Rule::indent, "an indent")?
                     .as_str()
                     .len();
                 let item_pair = pairs
-                    .next_rule(Rule::item, "an item")
-                    .unwrap()
-                    .only_inner()
-                    .unwrap();
+                    .next_rule(Rule::item, "an item")?
+                    .only_inner()?;
                 let comment_opt = {
-                    let comment_pair = pairs.next_rule(Rule::comment, "a comment").unwrap();
+                    let comment_pair = pairs.next_rule(Rule::comment, "a comment")?;
                     let s = comment_pair.as_str();
                     (s.len() > 0).then_some(s.to_owned())
                 };
                 match item_pair.as_rule() {
                     Rule::branch => {
-                        let branch = item_pair.try_into_ast().unwrap();
+                        let branch = item_pair.try_into_ast()?;
                         let block = Block::new(branch);
                         Ok((spaces, Self::Block(block, comment_opt)))
                     }
                     Rule::stmt => {
-                        let stmt = item_pair.try_into_ast().unwrap();
+                        let stmt = item_pair.try_into_ast()?;
                         Ok((spaces, Self::Stmt(stmt, comment_opt)))
                     }
                     _ => Err(MypsError::pair_wrong_rule("a branch or unit", item_pair)),
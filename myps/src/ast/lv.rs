@@ -3,13 +3,34 @@ use regex::{Match, Regex};
 
 use ast_traits::{AstError, AstNode, AstPair, AstPairs, IntoAst};
 
-use crate::ast::{Dev, Num, Var};
+use crate::ast::{Dev, LogicType, Num, SlotLogicType, Var};
 use crate::{MypsError, MypsParser, MypsResult, Pair, Rule};
 
 #[derive(Clone, Debug)]
 pub enum Lv {
-    DevParam { dev: Dev, param: String },
-    NetParam { hash: Num, param: String },
+    DevParam {
+        dev: Dev,
+        param: LogicType,
+    },
+    DevSlot {
+        dev: Dev,
+        slot: Num,
+        param: SlotLogicType,
+    },
+    NetParam {
+        hash: Num,
+        param: LogicType,
+    },
+    NetSlot {
+        hash: Num,
+        slot: Num,
+        param: SlotLogicType,
+    },
+    NetName {
+        hash: Num,
+        name: String,
+        param: LogicType,
+    },
     Var(Var),
     Def(String),
 }
@@ -25,25 +46,46 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Lv {
 
     fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
         match pair.as_rule() {
-            Rule::lv | Rule::lv_var => pair.only_inner().unwrap().try_into_ast(),
+            Rule::lv | Rule::lv_var => pair.only_inner()?.try_into_ast(),
             Rule::lv_dev_param => {
                 let mut pairs = pair.into_inner();
-                let dev = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let param = pairs.final_pair().unwrap().try_into_ast().unwrap();
+                let dev = pairs.next_pair()?.try_into_ast()?;
+                let param = pairs.final_pair()?.try_into_ast()?;
                 Ok(Self::DevParam { dev, param })
             }
+            Rule::lv_dev_slot => {
+                let mut pairs = pair.into_inner();
+                let dev = pairs.next_pair()?.try_into_ast()?;
+                let slot = pairs.next_pair()?.try_into_ast()?;
+                let param = pairs.final_pair()?.try_into_ast()?;
+                Ok(Self::DevSlot { dev, slot, param })
+            }
             Rule::lv_net_param => {
                 let mut pairs = pair.into_inner();
-                let hash = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let param = pairs.final_pair().unwrap().try_into_ast().unwrap();
+                let hash = pairs.next_pair()?.try_into_ast()?;
+                let param = pairs.final_pair()?.try_into_ast()?;
                 Ok(Self::NetParam { hash, param })
             }
+            Rule::lv_net_slot => {
+                let mut pairs = pair.into_inner();
+                let hash = pairs.next_pair()?.try_into_ast()?;
+                let slot = pairs.next_pair()?.try_into_ast()?;
+                let param = pairs.final_pair()?.try_into_ast()?;
+                Ok(Self::NetSlot { hash, slot, param })
+            }
+            Rule::lv_net_name => {
+                let mut pairs = pair.into_inner();
+                let hash = pairs.next_pair()?.try_into_ast()?;
+                let name = pairs.next_pair()?.only_inner()?.as_str().to_owned();
+                let param = pairs.final_pair()?.try_into_ast()?;
+                Ok(Self::NetName { hash, name, param })
+            }
             Rule::lv_def => {
-                let name = pair.only_inner().unwrap().try_into_ast().unwrap();
+                let name = pair.only_inner()?.try_into_ast()?;
                 Ok(Self::Def(name))
             }
             Rule::var | Rule::var_fixed => {
-                let var = pair.try_into_ast::<Var>().unwrap();
+                let var = pair.try_into_ast::<Var>()?;
                 let invalid_name = RESERVED_NAME_PATTERN
                     .find_at(&var.key, 0)
                     .map(|m| m.end() == var.key.len())
@@ -3,7 +3,6 @@ use mips::ast::MipsNode;
 use mips::{Mips, MipsError, MipsParser, MipsResult, Rule as MipsRule};
 
 use crate::{MypsError, MypsParser, MypsResult, Pair, Rule};
-// use crate::lexer::MypsLexer;
 
 // Helper for From<{primitive}> for Num and Expr
 macro_rules! impl_from_primitive {
@@ -27,6 +26,11 @@ macro_rules! impl_from_primitive {
     };
 }
 
+pub use ast_common::{BatchMode, LogicType, ReagentMode, SlotLogicType};
+
+mod common;
+pub use common::Mode;
+
 mod var;
 pub use var::Var;
 
@@ -37,7 +41,7 @@ mod num;
 pub use num::Num;
 
 mod func;
-pub use func::{Arg, Func};
+pub use func::{Arg, Call, Func};
 
 mod expr;
 pub use expr::{BinaryOp, Expr, UnaryOp};
@@ -1,7 +1,9 @@
 use ast_traits::{AstError, AstNode, AstPair, AstPairs, IntoAst};
 use mips::MipsResult;
 
-use crate::ast::{Dev, Expr, Func, Lv, Rv, Var};
+use crate::ast::{
+    BatchMode, Call, Dev, Expr, Func, LogicType, Lv, Mode, ReagentMode, Rv, SlotLogicType, Var,
+};
 use crate::{MypsError, MypsParser, MypsResult, Pair, Pairs, Rule};
 
 #[derive(Clone, Debug)]
@@ -10,24 +12,31 @@ pub enum Num {
     Var(Var),
     Expr(Box<Expr>),
     Func(Box<Func>),
+    Call(Call),
     DevParam {
         dev: Dev,
-        param: String,
+        param: LogicType,
     },
     DevSlot {
         dev: Dev,
         slot: Box<Num>,
-        param: String,
+        param: SlotLogicType,
     },
     DevReagent {
         dev: Dev,
-        mode: Box<Num>,
+        mode: Mode<ReagentMode>,
         reagent: String,
     },
     NetParam {
         hash: Box<Num>,
-        param: String,
-        mode: Box<Num>,
+        param: LogicType,
+        mode: Mode<BatchMode>,
+    },
+    NetName {
+        hash: Box<Num>,
+        name: String,
+        param: LogicType,
+        mode: Mode<BatchMode>,
     },
 }
 
@@ -71,44 +80,49 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Num {
 
     fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
         match pair.as_rule() {
-            Rule::num_var | Rule::num | Rule::dev_net => pair.only_inner().unwrap().try_into_ast(),
+            Rule::num_var | Rule::num | Rule::dev_net => pair.only_inner()?.try_into_ast(),
             // A literal number (integer or floating-point)
-            Rule::int | Rule::dec => Ok(Self::Lit(pair.as_str().parse().unwrap())),
+            Rule::int | Rule::dec => Ok(Self::Lit(pair.as_str().parse()?)),
             // A variable token
-            Rule::var => Ok(Self::Var(pair.try_into_ast().unwrap())),
+            Rule::var => Ok(Self::Var(pair.try_into_ast()?)),
             // A parenthesized expression
             Rule::expr => {
-                let expr = pair.try_into_ast().unwrap();
+                let expr = pair.try_into_ast()?;
                 match expr {
                     Expr::Num(num) => Ok(num),
                     _ => Ok(Self::Expr(Box::new(expr))),
                 }
             }
-            // A builtin numeric r-value function (e.g. min, pop, sos)
+            // A builtin numeric r-value function (e.g. min, pop, sos), or otherwise a call of a
+            // function defined by the program
             Rule::num_func => {
-                let func = pair.try_into_ast().unwrap();
+                let name = pair.clone().into_inner().next_pair()?.as_str();
+                if !Func::NAMES.contains(&name) {
+                    return Ok(Self::Call(pair.try_into_ast()?));
+                }
+                let func = pair.try_into_ast()?;
                 Ok(Self::Func(Box::new(func)))
                 // let mut pairs = pair.into_inner();
-                // let name = pairs.next_pair().unwrap().try_into_ast().unwrap();
+                // let name = pairs.next_pair()?.try_into_ast()?;
                 // let args = pairs
                 //     .map(Rv::try_from_pair)
                 //     .collect::<MypsResult<Vec<Rv>>>()
-                //     .unwrap();
+                //     ?;
                 // Ok(Self::Func { name, args })
             }
             //  The value from reading the parameter of a device
             Rule::num_dev_param => {
                 let mut pairs = pair.into_inner();
-                let dev = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let param = pairs.final_pair().unwrap().try_into_ast().unwrap();
+                let dev = pairs.next_pair()?.try_into_ast()?;
+                let param = pairs.final_pair()?.try_into_ast()?;
                 Ok(Self::DevParam { dev, param })
             }
             //  The value from reading a slot parameter of a device
             Rule::num_dev_slot => {
                 let mut pairs = pair.into_inner();
-                let dev = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let slot = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let param = pairs.final_pair().unwrap().try_into_ast().unwrap();
+                let dev = pairs.next_pair()?.try_into_ast()?;
+                let slot = pairs.next_pair()?.try_into_ast()?;
+                let param = pairs.final_pair()?.try_into_ast()?;
 
                 let slot = Box::new(slot);
                 Ok(Self::DevSlot { dev, slot, param })
@@ -116,23 +130,37 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Num {
             //  The value from reading a reagent parameter of a device */
             Rule::num_dev_reagent => {
                 let mut pairs = pair.into_inner();
-                let dev = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let mode = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let reagent = pairs.final_pair().unwrap().try_into_ast().unwrap();
-
-                let mode = Box::new(mode);
+                let dev = pairs.next_pair()?.try_into_ast()?;
+                let mode = pairs.next_pair()?.try_into_ast()?;
+                let reagent = pairs.final_pair()?.try_into_ast()?;
                 Ok(Self::DevReagent { dev, mode, reagent })
             }
             //  The value from batch-reading the parameter of devices on the data network
             Rule::num_net_param => {
                 let mut pairs = pair.into_inner();
-                let hash = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let param = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let mode = pairs.final_pair().unwrap().try_into_ast().unwrap();
+                let hash = pairs.next_pair()?.try_into_ast()?;
+                let param = pairs.next_pair()?.try_into_ast()?;
+                let mode = pairs.final_pair()?.try_into_ast()?;
 
-                let (hash, mode) = (Box::new(hash), Box::new(mode));
+                let hash = Box::new(hash);
                 Ok(Self::NetParam { hash, param, mode })
             }
+            //  The value from batch-reading the parameter of named devices on the data network
+            Rule::num_net_name => {
+                let mut pairs = pair.into_inner();
+                let hash = pairs.next_pair()?.try_into_ast()?;
+                let name = pairs.next_pair()?.only_inner()?.as_str().to_owned();
+                let param = pairs.next_pair()?.try_into_ast()?;
+                let mode = pairs.final_pair()?.try_into_ast()?;
+
+                let hash = Box::new(hash);
+                Ok(Self::NetName {
+                    hash,
+                    name,
+                    param,
+                    mode,
+                })
+            }
             _ => Err(MypsError::pair_wrong_rule("a number-like", pair)),
         }
     }
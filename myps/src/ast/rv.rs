@@ -20,10 +20,10 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Rv {
 
     fn try_from_pair(pair: Pair) -> MypsResult<Self::Output> {
         match pair.as_rule() {
-            Rule::rv => pair.only_inner().unwrap().try_into_ast(),
-            Rule::expr => Ok(Self::Expr(pair.try_into_ast().unwrap())),
-            Rule::dev => Ok(Self::Dev(pair.try_into_ast().unwrap())),
-            Rule::var => Ok(Self::Var(pair.try_into_ast().unwrap())),
+            Rule::rv => pair.only_inner()?.try_into_ast(),
+            Rule::expr => Ok(Self::Expr(pair.try_into_ast()?)),
+            Rule::dev => Ok(Self::Dev(pair.try_into_ast()?)),
+            Rule::var => Ok(Self::Var(pair.try_into_ast()?)),
             _ => Err(MypsError::pair_wrong_rule(
                 "an r-value (device or expression)",
                 pair,
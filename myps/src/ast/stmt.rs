@@ -1,15 +1,28 @@
 use ast_traits::{AstError, AstNode, AstPair, AstPairs, IntoAst};
-use mips::MipsResult;
+use mips::{MipsError, MipsResult};
 
-use crate::ast::{BinaryOp, Expr, Lv, Rv, Var};
+use crate::ast::{BatchMode, BinaryOp, Call, Expr, Lv, Mode, Rv, Var};
 use crate::{MypsError, MypsParser, MypsResult, Pair, Rule};
 
 #[derive(Clone, Debug)]
 pub enum Stmt {
     Fix(Vec<String>),
     Asn(Lv, Rv),
-    SelfAsn { op: BinaryOp, lhs: Var, rhs: Expr },
+    LvRvAsn(Vec<Lv>, Vec<Rv>),
+    // The mode batch-reads the l-value, if it's the parameter of the devices of a hash
+    SelfAsn {
+        op: BinaryOp,
+        lhs: Lv,
+        mode_opt: Option<Mode<BatchMode>>,
+        rhs: Expr,
+    },
     Mips(mips::ast::Stmt),
+    Call(Call),
+    Return(Option<Expr>),
+    // An import of a file, by its path (replaced by the lexer with the imported items)
+    Import(String),
+    Break,
+    Continue,
     Empty,
 }
 
@@ -20,7 +33,7 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Stmt {
 
     fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
         match pair.as_rule() {
-            Rule::stmt => pair.only_inner().unwrap().try_into_ast(),
+            Rule::stmt => pair.only_inner()?.try_into_ast(),
             Rule::stmt_fix => {
                 let names = pair
                     .into_inner()
@@ -29,15 +42,38 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Stmt {
                 Ok(Self::Fix(names))
             }
             Rule::stmt_asn => {
-                let mut pairs = pair.into_inner();
-                let lv = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let rv = pairs.final_pair().unwrap().try_into_ast().unwrap();
-                Ok(Self::Asn(lv, rv))
+                let (lv_pairs, rv_pairs) = pair
+                    .into_inner()
+                    .partition::<Vec<Pair>, _>(|pair| matches!(pair.as_rule(), Rule::lv));
+                let (num_lv, num_rv) = (lv_pairs.len(), rv_pairs.len());
+                if num_lv != num_rv {
+                    return Err(MypsError::lv_rv_asn_wrong_num(num_lv, num_rv));
+                }
+                let mut lvs = lv_pairs
+                    .into_iter()
+                    .map(Lv::try_from_pair)
+                    .collect::<MypsResult<Vec<_>>>()?;
+                let mut rvs = rv_pairs
+                    .into_iter()
+                    .map(Rv::try_from_pair)
+                    .collect::<MypsResult<Vec<_>>>()?;
+                if lvs.len() == 1 {
+                    Ok(Self::Asn(lvs.pop().unwrap(), rvs.pop().unwrap()))
+                } else {
+                    Ok(Self::LvRvAsn(lvs, rvs))
+                }
             }
             Rule::stmt_self_asn => {
                 let mut pairs = pair.into_inner();
-                let lhs = pairs.next_pair().unwrap().try_into_ast().unwrap();
-                let op_pair = pairs.next_pair().unwrap();
+                let lhs = pairs.next_pair()?.try_into_ast()?;
+                let mut op_pair = pairs.next_pair()?;
+                let mode_opt = if op_pair.as_rule() == Rule::batch_mode {
+                    let mode = op_pair.try_into_ast()?;
+                    op_pair = pairs.next_pair()?;
+                    Some(mode)
+                } else {
+                    None
+                };
                 let op = match op_pair.as_rule() {
                     Rule::op_s_add => BinaryOp::Add,
                     Rule::op_s_sub => BinaryOp::Sub,
@@ -51,12 +87,23 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Stmt {
                         ))
                     }
                 };
-                let rhs = pairs.final_pair().unwrap().try_into_ast().unwrap();
-                Ok(Self::SelfAsn { op, lhs, rhs })
+                let rhs = pairs.final_pair()?.try_into_ast()?;
+                Ok(Self::SelfAsn {
+                    op,
+                    lhs,
+                    mode_opt,
+                    rhs,
+                })
             }
             Rule::stmt_mips => {
                 use pest::Parser;
 
+                // A call of a function defined by the program, unless named as an instruction
+                let name = pair.clone().into_inner().next_pair()?.as_str();
+                if !mips::ast::Stmt::NAMES.contains(&name) {
+                    return Ok(Self::Call(pair.try_into_ast()?));
+                }
+
                 let mips_str = itertools::join(
                     pair.into_inner()
                         .map(|pair| pair.as_str().to_owned())
@@ -64,12 +111,25 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Stmt {
                     " ",
                 );
                 let stmt_pair = mips::MipsParser::parse(mips::Rule::stmt, &mips_str)
-                    .unwrap()
-                    .only_pair()
-                    .unwrap();
-                let stmt = mips::ast::Stmt::try_from_pair(stmt_pair).unwrap();
+                    .map_err(MipsError::from)?
+                    .only_pair()?;
+                let stmt = mips::ast::Stmt::try_from_pair(stmt_pair)?;
                 Ok(Self::Mips(stmt))
             }
+            Rule::stmt_return => {
+                let expr_opt = pair
+                    .into_inner()
+                    .next()
+                    .map(Expr::try_from_pair)
+                    .transpose()?;
+                Ok(Self::Return(expr_opt))
+            }
+            Rule::stmt_import => {
+                let path_pair = pair.only_inner()?.only_inner()?;
+                Ok(Self::Import(path_pair.as_str().to_owned()))
+            }
+            Rule::stmt_break => Ok(Stmt::Break),
+            Rule::stmt_continue => Ok(Stmt::Continue),
             Rule::stmt_empty => Ok(Stmt::Empty),
             _ => Err(MypsError::pair_wrong_rule("a statement", pair)),
         }
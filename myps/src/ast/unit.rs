@@ -20,7 +20,7 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Unit {
 
     fn try_from_pair(pair: Pair<'i>) -> MypsResult<Self::Output> {
         match pair.as_rule() {
-            Rule::unit => pair.only_inner().unwrap().try_into_ast(),
+            Rule::unit => pair.only_inner()?.try_into_ast(),
             Rule::unit_lvrv_asn => {
                 let pairs = pair.into_inner();
                 let (lv_pairs, expr_pairs) = pairs
@@ -31,16 +31,16 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Unit {
                 let lvs = lv_pairs
                     .into_iter()
                     .map(Lv::try_from_pair)
-                    .collect::<MypsResult<Vec<_>>>().unwrap();
+                    .collect::<MypsResult<Vec<_>>>()?;
                 let exprs = expr_pairs
                     .into_iter()
                     .map(Expr::try_from_pair)
-                    .collect::<MypsResult<Vec<_>>>().unwrap();
+                    .collect::<MypsResult<Vec<_>>>()?;
                 Ok(Self::LvRvAsn(lvs, exprs))
             }
             Rule::unit_mips => {
                 let mut pairs = pair.into_inner();
-                let name = pairs.next_pair().unwrap().as_str().to_owned();
+                let name = pairs.next_pair()?.as_str().to_owned();
                 unreachable!();
                 // let args =
                 // pairs.map(Expr::try_from_pair).collect::<MypsResult<Vec<Expr>>>().unwrap();
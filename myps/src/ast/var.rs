@@ -19,7 +19,7 @@ impl<'i> AstNode<'i, Rule, MypsParser, MypsError> for Var {
         match pair.as_rule() {
             Rule::var_fixed | Rule::var => {
                 let fixed = matches!(pair.as_rule(), Rule::var_fixed);
-                let key = pair.only_inner().unwrap().try_into_ast().unwrap();
+                let key = pair.only_inner()?.try_into_ast()?;
                 Ok(Self { key, fixed })
             }
             _ => Err(MypsError::pair_wrong_rule("a variable", pair)),
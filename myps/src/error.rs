@@ -4,19 +4,35 @@ use std::{fmt, fmt::Display};
 
 type PegError = pest::error::Error<Rule>;
 
-use ast_traits::AstErrorBase;
+use ast_common::constants::ConstError;
+use ast_traits::{peg_message, AstErrorBase, SourceSpan};
+use mips::MipsError;
 
 use crate::ast::{Lv, Rv};
 use crate::{Pair, Rule};
 
 #[derive(Debug)]
 pub enum MypsError {
-    PegError(PegError),
+    PegError(Box<PegError>),
     IOError(IOError),
     ParseIntError(ParseIntError),
     ParseFloatError(ParseFloatError),
 
     AstErrorBase(AstErrorBase),
+    ConstError(ConstError),
+    MipsError(MipsError),
+
+    Syntax(String),
+    IndentInvalid(String),
+    IndentExpected(String),
+    ChainInvalid(String),
+    LoopExpected(String),
+    FnExpected(String),
+    FnNested(String),
+    FnArgInvalid(String),
+    ImportInvalid(String),
+    ImportCycle(String),
+    ImportNested(String),
 
     AliasUndefined(String),
 
@@ -28,10 +44,130 @@ pub enum MypsError {
     LvRvAsnWrongNum(String),
     LvRvAsnWrongLvForRvDev(String),
 
+    FnUndefined(String),
+    FnArgsWrongNum(String),
+    FnNoValue(String),
+    DefNotConst(String),
+    BatchModeMissing(String),
+
     Dummy,
+
+    /// An error located in the source
+    Spanned(Box<MypsError>, Box<SourceSpan>),
 }
 
 impl MypsError {
+    /// A syntax error from parsing a source.
+    pub fn syntax(e: PegError, source: &str) -> Self {
+        let span = SourceSpan::from_peg_error(&e, source);
+        Self::Syntax(format!("Syntax error: {}", peg_message(&e))).at(span)
+    }
+
+    pub fn indent_invalid(spaces: usize, indent_size: usize) -> Self {
+        Self::IndentInvalid(format!(
+            "Indent of {} spaces is not a multiple of {}",
+            spaces, indent_size
+        ))
+    }
+
+    pub fn indent_expected() -> Self {
+        Self::IndentExpected("Expected an indented block".to_owned())
+    }
+
+    pub fn chain_invalid(branch: &str) -> Self {
+        Self::ChainInvalid(format!("'{}' without a preceding 'if' or 'elif'", branch))
+    }
+
+    pub fn loop_expected(stmt: &str) -> Self {
+        Self::LoopExpected(format!("'{}' outside of a loop", stmt))
+    }
+
+    pub fn fn_expected() -> Self {
+        Self::FnExpected("'return' outside of a function".to_owned())
+    }
+
+    pub fn fn_nested(branch: &str) -> Self {
+        Self::FnNested(format!("'{}' can only be defined at the top level", branch))
+    }
+
+    pub fn fn_arg_invalid(name: &str, i: usize) -> Self {
+        Self::FnArgInvalid(format!(
+            "Argument {} of function '{}' is a device, but only numbers can be passed",
+            i + 1,
+            name
+        ))
+    }
+
+    pub fn import_invalid(path: &str, e: IOError) -> Self {
+        Self::ImportInvalid(format!("Couldn't import '{}': {}", path, e))
+    }
+
+    /// A cycle of imports, from the file importing itself back to it.
+    pub fn import_cycle(paths: &[String]) -> Self {
+        Self::ImportCycle(format!("Cyclic import: {}", paths.join(" -> ")))
+    }
+
+    pub fn import_nested() -> Self {
+        Self::ImportNested("'import' can only be at the top level".to_owned())
+    }
+
+    /// Locate the error at a span, unless it's already located.
+    pub fn at(self, span: SourceSpan) -> Self {
+        match self {
+            Self::Spanned(..) => self,
+            Self::MipsError(e) => Self::MipsError(e.at(span)),
+            _ => Self::Spanned(Box::new(self), Box::new(span)),
+        }
+    }
+
+    /// Locate the error on a line of a source.
+    ///
+    /// An error already located by a single-line parse keeps its column, but is moved to the line.
+    pub fn on_line(self, line: usize, text: &str) -> Self {
+        match self {
+            Self::Spanned(e, span) => Self::Spanned(e, Box::new(SourceSpan { line, ..*span })),
+            Self::MipsError(e) => Self::MipsError(e.on_line(line, text)),
+            _ => self.at(SourceSpan::line(line, text)),
+        }
+    }
+
+    /// Set the file of the span of a located error, unless it's already in a file (e.g. an
+    /// imported one).
+    pub fn in_file(self, file: &str) -> Self {
+        match self {
+            Self::Spanned(e, span) if span.file.is_none() => {
+                Self::Spanned(e, Box::new(span.with_file(file)))
+            }
+            Self::MipsError(e) => Self::MipsError(e.in_file(file)),
+            _ => self,
+        }
+    }
+
+    /// Fill in the source line of a located error whose text isn't known.
+    pub fn with_source(self, source: &str) -> Self {
+        match self {
+            Self::Spanned(e, span) if span.text.is_none() => {
+                let text = source
+                    .lines()
+                    .nth(span.line.saturating_sub(1))
+                    .unwrap_or("");
+                let file = span.file;
+                let span = SourceSpan::line(span.line, text);
+                Self::Spanned(e, Box::new(SourceSpan { file, ..span }))
+            }
+            Self::MipsError(e) => Self::MipsError(e.with_source(source)),
+            _ => self,
+        }
+    }
+
+    pub fn span(&self) -> Option<&SourceSpan> {
+        match self {
+            Self::Spanned(_, span) => Some(span.as_ref()),
+            Self::MipsError(e) => e.span(),
+            _ => None,
+        }
+    }
+
     pub fn alias_undefined(key: &String) -> Self {
         Self::AliasUndefined(format!("Alias '{}' undefined", key))
     }
@@ -75,27 +211,80 @@ impl MypsError {
             lv, rv
         ))
     }
+
+    pub fn fn_undefined(name: &str) -> Self {
+        Self::FnUndefined(format!("Function '{}' undefined", name))
+    }
+
+    pub fn fn_args_wrong_num(name: &str, expected: usize, found: usize) -> Self {
+        Self::FnArgsWrongNum(format!(
+            "Function '{}' expects {} arguments, found {}",
+            name, expected, found
+        ))
+    }
+
+    pub fn fn_no_value(name: &str) -> Self {
+        Self::FnNoValue(format!("Function '{}' doesn't return a value", name))
+    }
+
+    /// A constant (`def`) depending on something only known at runtime (e.g. "the variable 'x'").
+    pub fn def_not_const(key: &str, what: &str) -> Self {
+        Self::DefNotConst(format!(
+            "Constant '{}' depends on {}, which is only known at runtime",
+            key, what
+        ))
+    }
+
+    /// A compound assignment to the parameter of the devices of a hash, which has to say how to
+    /// batch-read the parameter.
+    pub fn batch_mode_missing() -> Self {
+        Self::BatchModeMissing(
+            "Compound assignment to a batch parameter needs a batch mode to read it with \
+             (e.g. `Hash.all.Setting.Maximum += 1`)"
+                .to_owned(),
+        )
+    }
 }
 
 impl Display for MypsError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::PegError(e) => write!(f, "{:?}", e),
-            Self::IOError(e) => write!(f, "{:?}", e),
+            Self::IOError(e) => write!(f, "{}", e),
             Self::ParseIntError(e) => write!(f, "{:?}", e),
             Self::ParseFloatError(e) => write!(f, "{:?}", e),
 
             Self::AstErrorBase(e) => write!(f, "{}", e),
+            Self::ConstError(e) => write!(f, "{}", e),
+            Self::MipsError(e) => write!(f, "{}", e),
 
-            Self::FuncUnknown(s)
+            Self::Syntax(s)
+            | Self::IndentInvalid(s)
+            | Self::IndentExpected(s)
+            | Self::ChainInvalid(s)
+            | Self::LoopExpected(s)
+            | Self::FnExpected(s)
+            | Self::FnNested(s)
+            | Self::FnArgInvalid(s)
+            | Self::ImportInvalid(s)
+            | Self::ImportCycle(s)
+            | Self::ImportNested(s)
+            | Self::FuncUnknown(s)
             | Self::FuncArgsWrongNum(s)
             | Self::FuncArgsWrongKinds(s)
             | Self::LvReservedName(s)
             | Self::AliasUndefined(s)
             | Self::LvRvAsnWrongNum(s)
-            | Self::LvRvAsnWrongLvForRvDev(s) => write!(f, "{}", s),
+            | Self::LvRvAsnWrongLvForRvDev(s)
+            | Self::FnUndefined(s)
+            | Self::FnArgsWrongNum(s)
+            | Self::FnNoValue(s)
+            | Self::DefNotConst(s)
+            | Self::BatchModeMissing(s) => write!(f, "{}", s),
 
             Self::Dummy => write!(f, "dummy"),
+
+            Self::Spanned(e, span) => write!(f, "{}\n{}", e, span),
         }
     }
 }
@@ -112,11 +301,18 @@ macro_rules! impl_from_error {
     }
 }
 
+impl From<PegError> for MypsError {
+    fn from(e: PegError) -> Self {
+        Self::PegError(Box::new(e))
+    }
+}
+
 impl_from_error!(
     MypsError,
-    PegError,
     IOError,
     AstErrorBase,
+    ConstError,
+    MipsError,
     ParseIntError,
     ParseFloatError,
 );
@@ -1,12 +1,12 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use maplit::btreemap;
 use pest::Parser;
 
-use ast_traits::{AstError, AstNode, AstPair, AstPairs, IntoAst};
+use ast_traits::{AstError, AstNode, AstPair, AstPairs, IntoAst, SourceSpan};
 // use mips::Alias;
 
 use crate::ast::*;
@@ -14,49 +14,156 @@ use crate::{MypsError, MypsParser, MypsResult, Pair, Rule};
 
 const INDENT_SIZE: usize = 4;
 
-pub fn lex_string(source: String) -> MypsResult<Item> {
+pub fn lex_string(source: String) -> Result<Item, Vec<MypsError>> {
     lex_lines(source.lines().map(str::to_owned))
 }
 
-pub fn lex_file<P: Into<PathBuf> + std::fmt::Debug>(path: P) -> MypsResult<Item> {
-    let path_string = format!("{:?}", path);
-    let f = File::open(path.into()).expect(&path_string);
+/// Lex the source of a file, resolving its imports relative to the file's path.
+///
+/// The path is only used for imports; the file isn't read.
+pub fn lex_source(source: String, path: &Path) -> Result<Item, Vec<MypsError>> {
+    let mut imports = Imports::new(path.to_path_buf());
+    lex_lines_in(source.lines().map(str::to_owned), &mut imports)
+}
+
+pub fn lex_file<P: Into<PathBuf> + std::fmt::Debug>(path: P) -> Result<Item, Vec<MypsError>> {
+    let path = path.into();
+    let f = File::open(&path).map_err(|e| vec![e.into()])?;
     let f = BufReader::new(f);
-    let lines = f.lines().collect::<Result<Vec<_>, _>>().unwrap();
-    lex_lines(lines.into_iter())
+    let lines = f
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| vec![e.into()])?;
+    let file = path.to_string_lossy();
+    lex_lines_in(lines.into_iter(), &mut Imports::new(path.clone()))
+        .map_err(|errors| errors.into_iter().map(|e| e.in_file(&file)).collect())
+}
+
+// Parse a source line into its indent (in spaces) and item
+fn parse_line(line_src: &str) -> MypsResult<(usize, Item)> {
+    let pair = MypsParser::parse(Rule::single_line, line_src)
+        .map_err(|e| MypsError::syntax(e, line_src))?
+        .only_rule(Rule::single_line, "a line")?;
+    Item::try_from_pair(pair)
 }
 
-pub fn lex_lines<'a>(line_iter: impl Iterator<Item = String>) -> MypsResult<Item> {
+/// Lex lines into a program item, resolving imports relative to the working directory.
+///
+/// A line with an error is skipped so that lexing can carry on, and all errors are returned.
+pub fn lex_lines<'a>(line_iter: impl Iterator<Item = String>) -> Result<Item, Vec<MypsError>> {
+    lex_lines_in(line_iter, &mut Imports::default())
+}
+
+// The files being imported (the innermost last), and the (canonical paths of the) files already
+// imported
+#[derive(Debug, Default)]
+struct Imports {
+    stack: Vec<PathBuf>,
+    imported: BTreeSet<PathBuf>,
+}
+
+impl Imports {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            stack: vec![path],
+            imported: BTreeSet::new(),
+        }
+    }
+}
+
+// Import a file: lex it into a block of its items, in which its errors are located, or nothing if
+// it was already imported (a file is only imported once, so that several files can import the
+// same one).
+//
+// The path is relative to the importing file (the last of the files being imported), and importing
+// one of the files being imported is a cycle.
+fn import_file(path: &str, imports: &mut Imports) -> Result<Option<Block>, Vec<MypsError>> {
+    let path_buf = match imports.stack.last().and_then(|importer| importer.parent()) {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    };
+    let file = path_buf.to_string_lossy().into_owned();
+    let source = std::fs::read_to_string(&path_buf)
+        .map_err(|e| vec![MypsError::import_invalid(&file, e)])?;
+    let canonical = path_buf.canonicalize().ok();
+    if let Some(i) = imports
+        .stack
+        .iter()
+        .position(|import| import.canonicalize().ok() == canonical)
+    {
+        let cycle = imports.stack[i..]
+            .iter()
+            .chain(std::iter::once(&path_buf))
+            .map(|import| import.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        return Err(vec![MypsError::import_cycle(&cycle)]);
+    }
+    if let Some(canonical) = canonical {
+        if !imports.imported.insert(canonical) {
+            return Ok(None);
+        }
+    }
+
+    imports.stack.push(path_buf);
+    let block_result = lex_block(source.lines().map(str::to_owned), imports);
+    imports.stack.pop();
+    let block = block_result.map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|e| e.in_file(&file))
+            .collect::<Vec<_>>()
+    })?;
+    let branch = Branch::Import { path: file, source };
+    Ok(Some(Block {
+        branch,
+        items: block.items,
+    }))
+}
+
+// Lex lines into a program block, its imports into blocks of their items
+fn lex_block(
+    line_iter: impl Iterator<Item = String>,
+    imports: &mut Imports,
+) -> Result<Block, Vec<MypsError>> {
     let mut block_stack: Vec<(Block, Option<String>)> = vec![(Block::new(Branch::Program), None)];
     let mut indent_stack = vec![0_usize];
     let mut curr_indent = 0_usize;
     let mut expect_indent = false;
+    // The line of the last block header, for reporting a missing indent at the end
+    let mut header = (0, String::new());
+    let mut errors = Vec::new();
 
     for (i, line_src) in line_iter.enumerate() {
-        let pair = MypsParser::parse(Rule::single_line, &line_src)
-            .unwrap()
-            .only_rule(Rule::single_line, "a line")
-            .unwrap();
-        let (spaces, item) = Item::try_from_pair(pair).unwrap();
+        let n = i + 1;
+        let (spaces, item) = match parse_line(&line_src) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                errors.push(e.on_line(n, &line_src));
+                continue;
+            }
+        };
 
         // Handle indent
         if !matches!(item, Item::Stmt(Stmt::Empty, _)) {
             let indent = if spaces % INDENT_SIZE != 0 {
-                panic!("Invalid indent size {} (line {})", spaces, i);
+                let span = SourceSpan::new(n, 1, spaces).with_text(&line_src);
+                errors.push(MypsError::indent_invalid(spaces, INDENT_SIZE).at(span));
+                continue;
             } else {
                 spaces / INDENT_SIZE
             };
+            if expect_indent && indent <= curr_indent {
+                errors.push(MypsError::indent_expected().on_line(n, &line_src));
+                // Close the block without a body, to carry on as if it had one
+                let (block, comment_opt) = block_stack.pop().unwrap();
+                let (head, _) = block_stack.last_mut().unwrap();
+                head.items.push(Item::Block(block, comment_opt));
+                expect_indent = false;
+            }
             if expect_indent {
-                if indent <= curr_indent {
-                    panic!(
-                        "Expected indent (line {}, {}, {}, {:?})",
-                        i, indent, curr_indent, indent_stack
-                    );
-                } else {
-                    indent_stack.push(indent);
-                    curr_indent = indent;
-                    expect_indent = false;
-                }
+                indent_stack.push(indent);
+                curr_indent = indent;
+                expect_indent = false;
             } else {
                 if indent < *indent_stack.last().unwrap() {
                     // Remove empties from previous head
@@ -86,23 +193,84 @@ pub fn lex_lines<'a>(line_iter: impl Iterator<Item = String>) -> MypsResult<Item
         // Place item
         match item {
             Item::Block(block, comment_opt) => {
+                // An elif or else has to follow an if or elif
+                if block.is_elif() || block.is_else() {
+                    let (head, _) = block_stack.last().unwrap();
+                    let follows_chain = head
+                        .items
+                        .iter()
+                        .rfind(|item| item.is_not_empty())
+                        .map_or(false, |item| item.is_if() || item.is_elif());
+                    if !follows_chain {
+                        let branch = if block.is_elif() { "elif" } else { "else" };
+                        errors.push(MypsError::chain_invalid(branch).on_line(n, &line_src));
+                    }
+                }
+                // A fn or macro has to be at the top level
+                if (block.branch.is_fn() || block.branch.is_macro()) && block_stack.len() > 1 {
+                    let branch = if block.branch.is_fn() { "fn" } else { "macro" };
+                    errors.push(MypsError::fn_nested(branch).on_line(n, &line_src));
+                }
                 block_stack.push((block, comment_opt));
                 expect_indent = true;
+                header = (n, line_src);
             }
             Item::Stmt(..) => {
                 if !matches!(item, Item::Stmt(Stmt::Empty, _)) {
                     expect_indent = false;
                 }
-                match item {
-                    _ => {}
+                // A break or continue has to be within a loop (at any depth)
+                let loop_stmt_opt = match item {
+                    Item::Stmt(Stmt::Break, _) => Some("break"),
+                    Item::Stmt(Stmt::Continue, _) => Some("continue"),
+                    _ => None,
+                };
+                if let Some(loop_stmt) = loop_stmt_opt {
+                    if !block_stack.iter().any(|(block, _)| block.branch.is_loop()) {
+                        errors.push(MypsError::loop_expected(loop_stmt).on_line(n, &line_src));
+                    }
+                }
+                // A return has to be within a fn or macro
+                if matches!(item, Item::Stmt(Stmt::Return(..), _))
+                    && !block_stack
+                        .iter()
+                        .any(|(block, _)| block.branch.is_fn() || block.branch.is_macro())
+                {
+                    errors.push(MypsError::fn_expected().on_line(n, &line_src));
                 }
+                // An import has to be at the top level, and is replaced by the imported items
+                // (whose errors are already located in the imported file), or by nothing if the
+                // file was already imported
+                let item = match item {
+                    Item::Stmt(Stmt::Import(path), comment_opt) => {
+                        if block_stack.len() > 1 {
+                            errors.push(MypsError::import_nested().on_line(n, &line_src));
+                            continue;
+                        }
+                        match import_file(&path, imports) {
+                            Ok(Some(block)) => Item::Block(block, comment_opt),
+                            Ok(None) => Item::Stmt(Stmt::Empty, comment_opt),
+                            Err(import_errors) => {
+                                let span = SourceSpan::line(n, &line_src);
+                                let import_errors = import_errors.into_iter();
+                                errors.extend(import_errors.map(|e| e.at(span.clone())));
+                                continue;
+                            }
+                        }
+                    }
+                    _ => item,
+                };
                 let (head, _) = block_stack.last_mut().unwrap();
                 head.items.push(item);
             }
         }
     }
     if expect_indent {
-        panic!("Expected indent");
+        let (n, line_src) = header;
+        errors.push(MypsError::indent_expected().on_line(n, &line_src));
+    }
+    if !errors.is_empty() {
+        return Err(errors);
     }
     while block_stack.len() > 1 {
         // indent_stack.pop();
@@ -111,9 +279,19 @@ pub fn lex_lines<'a>(line_iter: impl Iterator<Item = String>) -> MypsResult<Item
         head.items.push(Item::Block(block, comment_opt));
     }
     let (program_block, _) = block_stack.pop().unwrap();
+    Ok(program_block)
+}
+
+// Lex lines into a program item, resolving imports relative to the last of the files being
+// imported
+fn lex_lines_in(
+    line_iter: impl Iterator<Item = String>,
+    imports: &mut Imports,
+) -> Result<Item, Vec<MypsError>> {
+    let program_block = lex_block(line_iter, imports)?;
     let mut program_item = Item::Block(program_block, None);
 
-    // Validate/update if-elif-else chains
+    // Validate/update if-elif-else chains (of imported items too, so that their ids are unique)
     fn validate_chains(item: &mut Item, next_chain_id: &mut usize) -> MypsResult<()> {
         if let Item::Block(block, ..) = item {
             let items = &mut block.items;
@@ -161,7 +339,8 @@ pub fn lex_lines<'a>(line_iter: impl Iterator<Item = String>) -> MypsResult<Item
                                 },
                                 ..,
                             ) => {
-                                *chain_id = prev_chain_id.expect("elif without an if");
+                                *chain_id = prev_chain_id
+                                    .ok_or_else(|| MypsError::chain_invalid("elif"))?;
                                 if next_is_elif_else {
                                     *end_chain = false;
                                 }
@@ -173,17 +352,19 @@ pub fn lex_lines<'a>(line_iter: impl Iterator<Item = String>) -> MypsResult<Item
                                 },
                                 ..,
                             ) => {
-                                *chain_id = prev_chain_id.expect("else without an if");
+                                *chain_id = prev_chain_id
+                                    .ok_or_else(|| MypsError::chain_invalid("else"))?;
                             }
                             _ => unreachable!(),
                         }
                     } else {
                         let j = i + 1;
                         if j < items.len() && items[i + 1].is_elif_else() {
-                            panic!();
+                            let branch = if items[j].is_elif() { "elif" } else { "else" };
+                            return Err(MypsError::chain_invalid(branch));
                         }
                     }
-                    validate_chains(&mut items[i], next_chain_id).unwrap();
+                    validate_chains(&mut items[i], next_chain_id)?;
                 }
             }
         }
@@ -191,7 +372,7 @@ pub fn lex_lines<'a>(line_iter: impl Iterator<Item = String>) -> MypsResult<Item
     }
 
     let mut next_chain_id = 0_usize;
-    validate_chains(&mut program_item, &mut next_chain_id).unwrap();
+    validate_chains(&mut program_item, &mut next_chain_id).map_err(|e| vec![e])?;
 
     let mut fixed_map = BTreeMap::<String, bool>::new();
 
@@ -211,6 +392,13 @@ pub fn lex_lines<'a>(line_iter: impl Iterator<Item = String>) -> MypsResult<Item
                     Stmt::Asn(Lv::Var(Var { key, fixed }), _) => {
                         *fixed = *fixed || *fixed_map.get(key).unwrap_or(&false);
                     }
+                    Stmt::LvRvAsn(lvs, _) => {
+                        for lv in lvs.iter_mut() {
+                            if let Lv::Var(Var { key, fixed }) = lv {
+                                *fixed = *fixed || *fixed_map.get(key).unwrap_or(&false);
+                            }
+                        }
+                    }
                     _ => {}
                 }
                 // unimplemented!();
@@ -221,261 +409,78 @@ pub fn lex_lines<'a>(line_iter: impl Iterator<Item = String>) -> MypsResult<Item
     Ok(program_item)
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum Alias {
-    Num(f64),
-    Var(bool),
-}
-
-#[derive(Clone, Debug)]
-pub struct MypsLexer {
-    pub var_flags: BTreeMap<String, (usize, u8)>,
-}
-
-const VAR_ASSIGNED: u8 = 1;
-const VAR_READ: u8 = 2;
-
-impl MypsLexer {
-    pub fn new() -> Self {
-        let var_flags = BTreeMap::new();
-        Self { var_flags }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn parse_and_lex_program_str(
-        &mut self,
-        source: &str,
-        indent_size: usize,
-    ) -> MypsResult<Item> {
-        let mut program_item = self.parse_program_str(source, indent_size).unwrap();
-        let mut aliases = BTreeMap::new();
-        self.lex_item(&mut program_item, &mut aliases).unwrap();
-        Ok(program_item)
+    // The path of a test script
+    fn script(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("test-scripts")
+            .join(path)
     }
 
-    pub fn parse_program_str(&mut self, source: &str, indent_size: usize) -> MypsResult<Item> {
-        let mut block_stack: Vec<(Block, Option<String>)> =
-            vec![(Block::new(Branch::Program), None)];
-        let mut indent_stack = vec![0_usize];
-        let mut curr_indent = 0_usize;
-        let mut expect_indent = false;
-        // Parse lines and lex scopes and indentations
-        for (i, line_src) in source.trim_end().split("\n").enumerate() {
-            // Parse line string into a pair
-            let pair = MypsParser::parse(Rule::single_line, line_src)
-                .unwrap()
-                .only_rule(Rule::single_line, "a line")
-                .unwrap();
-            // Parse line pair into number of left-spaces and an Item
-            let (spaces, item) = Item::try_from_pair(pair).unwrap();
-            // Lex scopes and indentation
-
-            if !matches!(item, Item::Stmt(Stmt::Empty, _)) {
-                let indent = if spaces % indent_size != 0 {
-                    panic!("Invalid indent size {} (line {})", spaces, i);
-                } else {
-                    spaces / indent_size
+    // The paths of the imported blocks of an item, in order
+    fn import_paths(item: &Item) -> Vec<String> {
+        match item {
+            Item::Block(block, ..) => {
+                let own = match &block.branch {
+                    Branch::Import { path, .. } => vec![path.clone()],
+                    _ => Vec::new(),
                 };
-                if expect_indent {
-                    if indent <= curr_indent {
-                        panic!(
-                            "Expected indent (line {}, {}, {}, {:?})",
-                            i, indent, curr_indent, indent_stack
-                        );
-                    } else {
-                        indent_stack.push(indent);
-                        curr_indent = indent;
-                        expect_indent = false;
-                    }
-                } else {
-                    if indent < *indent_stack.last().unwrap() {
-                        // Remove empties from previous head
-                        let empties = {
-                            let (head, _) = block_stack.last_mut().unwrap();
-                            let i = 1 + head
-                                .items
-                                .iter()
-                                .rposition(|item| !matches!(item, Item::Stmt(Stmt::Empty, _)))
-                                .unwrap_or(head.items.len());
-                            head.items.split_off(i)
-                        };
-                        // Pop indents and blocks until at new head
-                        while indent < *indent_stack.last().unwrap() {
-                            indent_stack.pop();
-                            let (block, comment_opt) = block_stack.pop().unwrap();
-                            let (head, _) = block_stack.last_mut().unwrap();
-                            head.items.push(Item::Block(block, comment_opt));
-                            curr_indent = *indent_stack.last().unwrap();
-                        }
-                        // Added removed empties to new head
-                        let (head, _) = block_stack.last_mut().unwrap();
-                        head.items.extend(empties);
-                    }
-                }
-            }
-            // Place item
-            match item {
-                Item::Block(block, comment_opt) => {
-                    block_stack.push((block, comment_opt));
-                    expect_indent = true;
-                }
-                Item::Stmt(..) => {
-                    if !matches!(item, Item::Stmt(Stmt::Empty, _)) {
-                        expect_indent = false;
-                    }
-                    match item {
-                        _ => {}
-                    }
-                    let (head, _) = block_stack.last_mut().unwrap();
-                    head.items.push(item);
-                }
+                own.into_iter()
+                    .chain(block.items.iter().flat_map(import_paths))
+                    .collect()
             }
+            _ => Vec::new(),
         }
-        if expect_indent {
-            panic!("Expected indent");
-        }
-        while block_stack.len() > 1 {
-            // indent_stack.pop();
-            let (block, comment_opt) = block_stack.pop().unwrap();
-            let (head, _) = block_stack.last_mut().unwrap();
-            head.items.push(Item::Block(block, comment_opt));
-        }
-        let (program_block, _) = block_stack.pop().unwrap();
-        let program_item = Item::Block(program_block, None);
-        Ok(program_item)
     }
 
-    pub fn lex_item(
-        &mut self,
-        item: &mut Item,
-        aliases: &mut BTreeMap<String, Alias>,
-    ) -> Result<(), String> {
-        match item {
-            Item::Block(Block { branch, items }, _) => {
-                let mut aliases = aliases.clone();
-                match branch {
-                    Branch::While { cond } => {
-                        unimplemented!();
-                    }
-                    Branch::If { cond, .. } => {
-                        unimplemented!();
-                    }
-                    Branch::Elif { cond, .. } => {
-                        unimplemented!();
-                    }
-                    Branch::For(var, s, e, step) => {
-                        unimplemented!();
-                    }
-                    _ => {}
-                }
-                for (j, item) in items.iter_mut().enumerate() {
-                    self.lex_item(item, &mut aliases).unwrap();
-                }
-            }
-            Item::Stmt(stmt, _) => match stmt {
-                Stmt::Fix(names) => {
-                    // for name in names.iter() {
-                    //     self.var_flags.try_insert(name.clone(), (i, 0)).ok();
-                    // }
-                }
-                Stmt::Asn(lv, rv) => {
-                    if let Lv::Var(Var { key, fixed }) = lv {
-                        let fixed = *fixed
-                            || aliases
-                                .get(key)
-                                .map(|alias| {
-                                    if let Alias::Var(fixed) = alias {
-                                        *fixed
-                                    } else {
-                                        false
-                                    }
-                                })
-                                .unwrap_or(false);
-
-                        // if self.var_flags.contains_key(key) {
-                        //     let (_, flag) = self.var_flags.get_mut(key).unwrap();
-                        //     *flag |= VAR_ASSIGNED;
-                        // } else {
-                        //     self.var_flags.insert(key.clone(), (i, VAR_ASSIGNED));
-                        // }
-                        aliases.insert(key.clone(), Alias::Var(fixed));
-                    }
-                }
-                Stmt::SelfAsn { lhs, rhs, .. } => {
-                    unimplemented!();
-                }
-                _ => {}
-            },
+    #[test]
+    fn import_diamond() {
+        let program = lex_file(script("import/diamond/main.myps")).unwrap();
+        // The library imported by both files is only imported (by the first) once
+        let paths = import_paths(&program);
+        let files = ["b.myps", "lib.myps", "c.myps"];
+        assert_eq!(paths.len(), files.len(), "{:?}", paths);
+        for (path, file) in paths.iter().zip(files.iter()) {
+            assert!(Path::new(path).ends_with(file), "{:?}", paths);
         }
-        Ok(())
     }
 
-    fn lex_rv(&mut self, rv: &Rv) {
-        match rv {
-            Rv::Expr(expr) => {
-                unimplemented!();
-            }
-            Rv::Dev(dev) => {
-                unimplemented!();
-            }
-            Rv::Var(var) => {
-                unimplemented!();
-            }
-        }
+    #[test]
+    fn import_cycle() {
+        let errors = lex_file(script("import/cycle/a.myps")).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let message = errors[0].to_string();
+        assert!(message.starts_with("Cyclic import: "), "{}", message);
+        assert!(message.contains("a.myps -> "), "{}", message);
+        assert!(message.contains("b.myps -> "), "{}", message);
     }
 
-    fn lex_expr(&mut self, expr: &Expr) {
-        match expr {
-            Expr::Num(num) => {
-                unimplemented!();
-            }
-            Expr::Unary { op, rhs } => {
-                unimplemented!();
-            }
-            Expr::Binary { op, lhs, rhs } => {
-                unimplemented!();
-            }
-            Expr::Ternary { cond, if_t, if_f } => {
-                unimplemented!();
-            }
-        }
+    #[test]
+    fn import_error_location() {
+        // An error in an imported file is located in that file, not at the import
+        let errors = lex_file(script("import/error/main.myps")).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let span = errors[0].span().unwrap();
+        let file = span.file.as_deref().unwrap_or("");
+        assert!(file.ends_with("bad.myps"), "{}", file);
+        assert_eq!((span.line, span.col), (4, 9));
+        assert_eq!(span.text.as_deref(), Some("d0.On = $"));
     }
 
-    fn lex_num(&mut self, num: &Num) {
-        match num {
-            Num::Lit(n) => {
-                unimplemented!();
-            }
-            Num::Var(var) => {
-                unimplemented!();
-            }
-            Num::Expr(box expr) => {
-                unimplemented!();
-            }
-            Num::Func(box func) => {
-                unimplemented!();
-            }
-            Num::DevParam { dev, param } => {
-                unimplemented!();
-            }
-            Num::DevSlot {
-                dev, slot: box num, ..
-            } => {
-                unimplemented!();
-            }
-            Num::DevReagent {
-                dev,
-                mode: box mode,
-                ..
-            } => {
-                unimplemented!();
-            }
-            Num::NetParam {
-                hash: box hash,
-                mode: box mode,
-                ..
-            } => {
-                unimplemented!();
-            }
-        }
+    #[test]
+    fn import_missing() {
+        let errors = lex_file(script("import/missing/main.myps")).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let message = errors[0].to_string();
+        assert!(message.starts_with("Couldn't import '"), "{}", message);
+        assert!(message.contains("nowhere.myps': "), "{}", message);
+        // The error is located at the import
+        let span = errors[0].span().unwrap();
+        let file = span.file.as_deref().unwrap_or("");
+        assert!(file.ends_with("main.myps"), "{}", file);
+        assert_eq!(span.line, 2);
     }
 }
@@ -9,16 +9,82 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::convert::{TryFrom, TryInto};
 use std::ops::Range;
+use std::path::Path;
 
 use maplit::{btreemap, btreeset};
 
-use mips::ast::{DevBase, FixMode, MipsNode, RegBase, RegLit};
-use mips::{Alias, Aliases, Mips, MipsError, MipsResult};
+use ast_common::constants;
+use ast_traits::SourceSpan;
+use mips::ast::{DevBase, FixMode, LogicType, MipsNode, RegBase, RegLit, SlotLogicType};
+use mips::{Alias, Aliases, Mips, MipsError, OptimizationConfig, OptimizationStats};
+use myps::{MypsError, MypsResult};
 
 #[derive(Clone, Debug)]
 pub struct Translator {
     aliases: Aliases,
     next_index: usize,
+    // The source line of the last item translated, for locating errors
+    line: usize,
+    next_loop_id: usize,
+    // The loops being translated, innermost last
+    loops: Vec<LoopTags>,
+    functions: BTreeMap<String, Function>,
+    // The function being translated, by name
+    function_opt: Option<(String, Function)>,
+    macros: BTreeMap<String, Macro>,
+    next_macro_id: usize,
+    // The macros being expanded, innermost last
+    expansions: Vec<Expansion>,
+    // The path and source of the imported file being translated
+    import_opt: Option<(String, String)>,
+}
+
+// A loop being translated, and whether its body jumps to its end (break) or to its next
+// iteration (continue), which need tags
+#[derive(Clone, Debug)]
+struct LoopTags {
+    id: usize,
+    breaks: bool,
+    continues: bool,
+}
+
+// A function defined by the program.
+//
+// Arguments are passed in the registers of the parameters and the value is returned in the return
+// register, all of which belong to the function along with the registers of its body (a range of
+// indices). These registers are scoped over the body and all calls of the function (including
+// those of functions which call it), so that they aren't shared with any register which is live
+// across a call.
+#[derive(Clone, Debug)]
+struct Function {
+    params: Vec<RegBase>,
+    ret: RegBase,
+    // Whether any return has a value
+    returns: bool,
+    indices: Range<usize>,
+    // The functions called by the body, directly or not
+    callees: BTreeSet<String>,
+}
+
+// A macro defined by the program, whose body is translated (expanded) at each call
+#[derive(Clone, Debug)]
+struct Macro {
+    params: Vec<String>,
+    items: Vec<myps::ast::Item>,
+    // The source line of the header
+    line: usize,
+    // The path and source of the imported file defining the macro, if not the program
+    import_opt: Option<(String, String)>,
+}
+
+// A macro being expanded: the register of its value, and the number of jumps (returns) to the tag
+// after the expanded body
+#[derive(Clone, Debug)]
+struct Expansion {
+    name: String,
+    ret: RegBase,
+    id: usize,
+    jumps: usize,
 }
 
 impl Default for Translator {
@@ -27,10 +93,22 @@ impl Default for Translator {
         Self {
             aliases,
             next_index: 0,
+            line: 0,
+            next_loop_id: 0,
+            loops: Vec::new(),
+            functions: BTreeMap::new(),
+            function_opt: None,
+            macros: BTreeMap::new(),
+            next_macro_id: 0,
+            expansions: Vec::new(),
+            import_opt: None,
         }
     }
 }
 
+// The statements of an assignment, and the alias of the variable it assigns (if any)
+type Assignment = (Vec<mips::ast::Stmt>, Option<(String, Alias)>);
+
 fn shift_scope(scope: &mut Range<usize>, offset: usize) {
     scope.start += offset;
     scope.end += offset;
@@ -50,6 +128,37 @@ fn shift_scopes(lines: &mut Vec<mips::ast::Line>, offset: usize) {
     }
 }
 
+fn end_loop_tag(id: usize) -> String {
+    format!("endLoop{}", id)
+}
+
+fn next_loop_tag(id: usize) -> String {
+    format!("nextLoop{}", id)
+}
+
+fn end_macro_tag(id: usize) -> String {
+    format!("endMacro{}", id)
+}
+
+fn tag_line(tag: String) -> mips::ast::Line {
+    mips::ast::Line::new_no_comment(mips::ast::Stmt::Tag([tag.into()]))
+}
+
+// The value of a translated number, if it's known at compile time
+fn lit_value(num: &mips::ast::Num, stmts: &[mips::ast::Stmt]) -> Option<f64> {
+    match num {
+        mips::ast::Num::Lit(n) if stmts.is_empty() => Some(*n),
+        _ => None,
+    }
+}
+
+fn arg_lit_value(arg: &mips::ast::Arg, stmts: &[mips::ast::Stmt]) -> Option<f64> {
+    match arg {
+        mips::ast::Arg::Num(num) => lit_value(num, stmts),
+        _ => None,
+    }
+}
+
 fn compare_scopes(lhs: &Range<usize>, rhs: &Range<usize>) -> std::cmp::Ordering {
     use std::cmp::Ordering;
 
@@ -84,8 +193,9 @@ impl Translator {
         self.aliases.get(key)
     }
 
-    fn try_get_alias(&self, key: &String) -> MipsResult<&Alias> {
-        self.get_alias(key).ok_or(MipsError::alias_undefined(key))
+    fn try_get_alias(&self, key: &String) -> MypsResult<&Alias> {
+        self.get_alias(key)
+            .ok_or_else(|| MipsError::alias_undefined(key).into())
     }
 
     fn has_reg(&self, key: &String) -> bool {
@@ -94,8 +204,8 @@ impl Translator {
             .unwrap_or(false)
     }
 
-    fn try_has_reg(&self, key: &String) -> MipsResult<bool> {
-        let alias = self.try_get_alias(key).unwrap();
+    fn try_has_reg(&self, key: &String) -> MypsResult<bool> {
+        let alias = self.try_get_alias(key)?;
         Ok(matches!(alias, Alias::Reg(..)))
     }
 
@@ -107,12 +217,12 @@ impl Translator {
         }
     }
 
-    fn try_get_reg(&self, key: &String) -> MipsResult<mips::ast::RegBase> {
+    fn try_get_reg(&self, key: &String) -> MypsResult<mips::ast::RegBase> {
         let alias = self.try_get_alias(key)?;
         if let Alias::Reg(reg_base) = alias {
             Ok(*reg_base)
         } else {
-            Err(MipsError::alias_wrong_kind("a register", alias))
+            Err(MipsError::alias_wrong_kind("a register", alias).into())
         }
     }
 
@@ -121,7 +231,7 @@ impl Translator {
         key: &String,
         indirections: usize,
         fixed: bool,
-    ) -> MipsResult<mips::ast::RegBase> {
+    ) -> MypsResult<mips::ast::RegBase> {
         if let Some(Alias::Reg(reg_base)) = self.get_alias(key) {
             let mut reg_base = *reg_base;
             reg_base.set_fixed(reg_base.fixed() || fixed);
@@ -132,21 +242,21 @@ impl Translator {
         }
     }
 
-    fn try_has_dev(&self, key: &String) -> MipsResult<()> {
+    fn try_has_dev(&self, key: &String) -> MypsResult<()> {
         let alias = self.try_get_alias(key)?;
         if matches!(alias, Alias::Dev(..)) {
             Ok(())
         } else {
-            Err(MipsError::alias_wrong_kind("a device", alias))
+            Err(MipsError::alias_wrong_kind("a device", alias).into())
         }
     }
 
-    fn try_get_dev_base(&self, key: &String) -> MipsResult<mips::ast::DevBase> {
+    fn try_get_dev_base(&self, key: &String) -> MypsResult<mips::ast::DevBase> {
         let alias = self.try_get_alias(key)?;
         if let Alias::Dev(dev) = alias {
             Ok(dev.clone())
         } else {
-            Err(MipsError::alias_wrong_kind("a device", alias))
+            Err(MipsError::alias_wrong_kind("a device", alias).into())
         }
     }
 
@@ -159,21 +269,37 @@ impl Translator {
         })
     }
 
-    pub fn translate_item(&mut self, item: myps::ast::Item) -> MipsResult<Vec<mips::ast::Line>> {
+    pub fn translate_item(&mut self, item: myps::ast::Item) -> MypsResult<Vec<mips::ast::Line>> {
         use mips::ast::{Arg, FixMode, Line, RegLit};
-        use myps::ast::{Block, Item};
+        use myps::ast::{Block, Branch, Item};
 
-        // Collect lines from inner items
+        // Collect lines from inner items. Items are translated in source order, each statement
+        // and block header (but the program) being one source line.
         let (mut lines, comment_opt) = match item {
             Item::Block(block, comment_opt) => {
-                let lines = self.translate_block(block).unwrap();
+                if !matches!(block.branch, Branch::Program) {
+                    self.line += 1;
+                }
+                // Errors not located within the body are from the header
+                let header = self.line;
+                let span = SourceSpan::new(header, 1, 0);
+                let mut lines = self.translate_block(block).map_err(|e| e.at(span))?;
+                // Lines not translated from the body are from the header
+                for line in lines.iter_mut() {
+                    line.source_line.get_or_insert(header);
+                }
                 (lines, comment_opt)
             }
             Item::Stmt(stmt, comment_opt) => {
-                let stmts = self.translate_stmt(stmt).unwrap();
+                self.line += 1;
+                let span = SourceSpan::new(self.line, 1, 0);
+                let stmts = self.translate_stmt(stmt).map_err(|e| e.at(span))?;
                 let lines = stmts
                     .into_iter()
-                    .map(Line::new_no_comment)
+                    .map(|stmt| Line {
+                        source_line: Some(self.line),
+                        ..Line::new_no_comment(stmt)
+                    })
                     .collect::<Vec<_>>();
                 (lines, comment_opt)
             }
@@ -187,14 +313,14 @@ impl Translator {
         Ok(lines)
     }
 
-    fn translate_items(&mut self, items: Vec<myps::ast::Item>) -> MipsResult<Vec<mips::ast::Line>> {
+    fn translate_items(&mut self, items: Vec<myps::ast::Item>) -> MypsResult<Vec<mips::ast::Line>> {
         use mips::ast::{Arg, FixMode, Line, Stmt};
         use myps::ast::{Block, Item};
 
         let mut lines = Vec::new();
         // let mut scopes = Vec::new();
         for item in items {
-            let mut item_lines = self.translate_item(item).unwrap();
+            let mut item_lines = self.translate_item(item)?;
             for line in item_lines.iter_mut() {
                 for arg in line.stmt.iter_args_mut() {
                     if let Some(reg_lit) = arg.as_reg_lit_mut() {
@@ -210,7 +336,262 @@ impl Translator {
         Ok(lines)
     }
 
-    fn translate_block(&mut self, block: myps::ast::Block) -> MipsResult<Vec<mips::ast::Line>> {
+    // Translate the body of a loop, along with which of the tags of the loop it jumps to
+    fn translate_loop_body(
+        &mut self,
+        items: Vec<myps::ast::Item>,
+    ) -> MypsResult<(Vec<mips::ast::Line>, LoopTags)> {
+        let id = self.next_loop_id;
+        self.next_loop_id += 1;
+        self.loops.push(LoopTags {
+            id,
+            breaks: false,
+            continues: false,
+        });
+        let lines_result = self.translate_items(items);
+        let loop_tags = self.loops.pop().unwrap();
+        Ok((lines_result?, loop_tags))
+    }
+
+    // Scope the registers of each function over its body and its calls (in the lines of the whole
+    // program)
+    fn scope_functions(&self, lines: &mut [mips::ast::Line]) -> MypsResult<()> {
+        use mips::ast::{Arg, LineAbs, Num, Stmt};
+
+        for (name, function) in self.functions.iter() {
+            let is_function_line = |line: &mips::ast::Line| -> MypsResult<bool> {
+                match &line.stmt {
+                    Stmt::Tag([Arg::String(tag)]) => Ok(tag == name),
+                    Stmt::Jal([Arg::LineAbs(LineAbs(Num::Alias(tag)))]) => {
+                        let callee = self
+                            .functions
+                            .get(tag)
+                            .ok_or_else(|| MypsError::fn_undefined(tag))?;
+                        Ok(tag == name || callee.callees.contains(name))
+                    }
+                    stmt => Ok(stmt.iter_args().any(|arg| match arg.as_reg_base() {
+                        Some(RegBase::Lit(reg_lit)) => function.indices.contains(&reg_lit.index),
+                        _ => false,
+                    })),
+                }
+            };
+            // The first and last lines of the function, its tag being among them
+            let mut bounds_opt = None;
+            for (i, line) in lines.iter().enumerate() {
+                if is_function_line(line)? {
+                    let (s, _) = bounds_opt.unwrap_or((i, i));
+                    bounds_opt = Some((s, i));
+                }
+            }
+            let (s, e) = bounds_opt.ok_or_else(|| MypsError::fn_undefined(name))?;
+            // Scoping the registers as l-values is enough, as for fixed registers
+            for line in lines.iter_mut() {
+                for arg in line.stmt.iter_args_mut() {
+                    if let Some(reg_lit) = arg.as_reg_lit_mut() {
+                        if function.indices.contains(&reg_lit.index) {
+                            reg_lit.fix_mode = FixMode::Scoped(s, e);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Translate a call of a function, into a register if its value is used
+    fn translate_call(
+        &mut self,
+        reg_base_opt: Option<mips::ast::RegBase>,
+        call: myps::ast::Call,
+        value: bool,
+    ) -> MypsResult<(mips::ast::Num, Vec<mips::ast::Stmt>)> {
+        use mips::ast::{Arg, LineAbs, Num, Stmt};
+
+        let myps::ast::Call { name, args } = call;
+        if let Some(mac) = self.macros.get(&name).cloned() {
+            return self.expand_macro(reg_base_opt, name, mac, args, value);
+        }
+        if matches!(&self.function_opt, Some((function_name, _)) if *function_name == name) {
+            return Err(MipsError::unsupported("A recursive call").into());
+        }
+        let function = self
+            .functions
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| MypsError::fn_undefined(&name))?;
+        if function.params.len() != args.len() {
+            let (expected, found) = (function.params.len(), args.len());
+            return Err(MypsError::fn_args_wrong_num(&name, expected, found));
+        }
+        if value && !function.returns {
+            return Err(MypsError::fn_no_value(&name));
+        }
+        // Evaluate all arguments before passing any, since an argument can call the function
+        let mut stmts = Vec::new();
+        let mut arg_nums = Vec::new();
+        for arg in args {
+            let (arg_num, arg_stmts) = self.translate_expr(None, arg)?;
+            stmts.extend(arg_stmts);
+            arg_nums.push(arg_num);
+        }
+        for (param, arg_num) in function.params.iter().zip(arg_nums) {
+            stmts.push(Stmt::Move([(*param).into(), arg_num.into()]));
+        }
+        // A call within a function saves the return address of the function
+        let nested = self.function_opt.is_some();
+        if let Some((_, caller)) = &mut self.function_opt {
+            caller.callees.insert(name.clone());
+            caller.callees.extend(function.callees.iter().cloned());
+        }
+        if nested {
+            stmts.push(Stmt::Push([RegBase::RA.into()]));
+        }
+        stmts.push(Stmt::Jal([Arg::LineAbs(LineAbs(name.into()))]));
+        if nested {
+            stmts.push(Stmt::Pop([RegBase::RA.into()]));
+        }
+        if value {
+            let reg_base = self.unwrap_reg_base(reg_base_opt);
+            let ret = Num::from(function.ret);
+            stmts.push(Stmt::Move([reg_base.into(), ret.into()]));
+            Ok((reg_base.into(), stmts))
+        } else {
+            Ok((Num::Lit(0_f64), stmts))
+        }
+    }
+
+    // Expand a call of a macro, into a register if its value is used.
+    //
+    // The body only sees the parameters along with the devices, constants and fixed variables of
+    // the program (as for a function), so that its variables can't clash with those of the caller.
+    fn expand_macro(
+        &mut self,
+        reg_base_opt: Option<mips::ast::RegBase>,
+        name: String,
+        mac: Macro,
+        args: Vec<myps::ast::Expr>,
+        value: bool,
+    ) -> MypsResult<(mips::ast::Num, Vec<mips::ast::Stmt>)> {
+        use mips::ast::{Arg, LineAbs, Num, Stmt};
+        use myps::ast::{Branch, Item, LineItem, Lv, Var};
+
+        let Macro {
+            params,
+            items,
+            line,
+            import_opt,
+        } = mac;
+        if self.expansions.iter().any(|exp| exp.name == name) {
+            return Err(MipsError::unsupported("A recursive call").into());
+        }
+        if params.len() != args.len() {
+            let (expected, found) = (params.len(), args.len());
+            return Err(MypsError::fn_args_wrong_num(&name, expected, found));
+        }
+        let line_items = || items.iter().flat_map(Item::iter);
+        let returns = line_items().any(|line_item| {
+            matches!(line_item, LineItem::Stmt(myps::ast::Stmt::Return(Some(..))))
+        });
+        if value && !returns {
+            return Err(MypsError::fn_no_value(&name));
+        }
+        let assigns = |key: &String| {
+            line_items().any(|line_item| match line_item {
+                LineItem::Stmt(myps::ast::Stmt::Asn(Lv::Var(Var { key: lv_key, .. }), _))
+                | LineItem::Stmt(myps::ast::Stmt::SelfAsn {
+                    lhs: Lv::Var(Var { key: lv_key, .. }),
+                    ..
+                })
+                | LineItem::Branch(Branch::For(Var { key: lv_key, .. }, ..)) => lv_key == key,
+                LineItem::Stmt(myps::ast::Stmt::LvRvAsn(lvs, _)) => lvs
+                    .iter()
+                    .any(|lv| matches!(lv, Lv::Var(Var { key: lv_key, .. }) if lv_key == key)),
+                _ => false,
+            })
+        };
+
+        // Bind the parameters to the arguments, copying a variable of the caller which the body
+        // assigns to
+        let mut stmts = Vec::new();
+        let mut bindings = Vec::new();
+        for (param, arg) in params.into_iter().zip(args) {
+            let (arg_num, arg_stmts) = self.translate_expr(None, arg)?;
+            let is_var = arg_stmts.is_empty();
+            stmts.extend(arg_stmts);
+            let alias = match arg_num {
+                Num::Lit(n) => Alias::Num(n),
+                Num::Reg(reg_base) if is_var && assigns(&param) => {
+                    let copy = self.next_reg_base(0, false);
+                    stmts.push(Stmt::Move([copy.into(), Num::from(reg_base).into()]));
+                    Alias::Reg(copy)
+                }
+                Num::Reg(reg_base) => Alias::Reg(reg_base),
+                Num::Alias(key) => self.try_get_alias(&key)?.clone(),
+            };
+            bindings.push((param, alias));
+        }
+        let aliases = self.aliases.clone();
+        self.aliases.map.retain(|_, alias| match alias {
+            Alias::Reg(reg_base @ RegBase::Lit(..)) => reg_base.fixed(),
+            _ => true,
+        });
+        for (param, alias) in bindings {
+            self.aliases.insert(param, alias);
+        }
+
+        // Translate the body, locating errors in it
+        let ret = self.unwrap_reg_base(reg_base_opt.filter(|_| value));
+        let id = self.next_macro_id;
+        self.next_macro_id += 1;
+        self.expansions.push(Expansion {
+            name,
+            ret,
+            id,
+            jumps: 0,
+        });
+        let call_line = self.line;
+        self.line = line;
+        let ends_with_return = matches!(
+            items.iter().rev().find(|item| item.is_not_empty()),
+            Some(Item::Stmt(myps::ast::Stmt::Return(..), _))
+        );
+        let body_result = self.translate_items(items).map_err(|e| match &import_opt {
+            Some((path, source)) => e.with_source(source).in_file(path),
+            None => e,
+        });
+        self.line = call_line;
+        let mut expansion = self.expansions.pop().unwrap();
+        self.aliases = aliases;
+        let mut body_lines = body_result?;
+
+        // A final return doesn't need to jump
+        if ends_with_return {
+            body_lines.pop();
+            expansion.jumps -= 1;
+        }
+        // The body is placed among the statements of the call, so its scopes are left to the
+        // block of the call
+        for line in body_lines.iter_mut() {
+            for arg in line.stmt.iter_args_mut() {
+                if let Some(reg_lit) = arg.as_reg_lit_mut() {
+                    if matches!(reg_lit.fix_mode, FixMode::Scoped(..)) {
+                        reg_lit.fix_mode = FixMode::Fixed;
+                    }
+                }
+            }
+        }
+        stmts.extend(body_lines.into_iter().map(|line| line.stmt));
+        if expansion.jumps > 0 {
+            stmts.push(Stmt::Tag([end_macro_tag(id).into()]));
+        }
+        if value {
+            Ok((ret.into(), stmts))
+        } else {
+            Ok((Num::Lit(0_f64), stmts))
+        }
+    }
+
+    fn translate_block(&mut self, block: myps::ast::Block) -> MypsResult<Vec<mips::ast::Line>> {
         use mips::ast::{Arg, Line, LineAbs, LineRel, Num, Reg, Stmt};
         use myps::ast::{Branch, Var};
         use std::iter::once;
@@ -229,8 +610,6 @@ impl Translator {
                 // is being thrown out, and so we need to decrement next_index.
                 translator.next_index -= 1;
                 let cond_stmt = match cond_stmt {
-                    Stmt::Sap (..)        => unimplemented!(),
-                    Stmt::Sapz(..)        => unimplemented!(),
                     Stmt::Sdns([_, d,  ]) => Stmt::Brdse([d,    c]),
                     Stmt::Sdse([_, d,  ]) => Stmt::Brdns([d,    c]),
                     Stmt::Seq ([_, a, b]) => Stmt::Brne ([a, b, c]),
@@ -243,8 +622,6 @@ impl Translator {
                     Stmt::Slez([_, a   ]) => Stmt::Brgtz([a,    c]),
                     Stmt::Slt ([_, a, b]) => Stmt::Brge ([a, b, c]),
                     Stmt::Sltz([_, a   ]) => Stmt::Brgez([a,    c]),
-                    Stmt::Sna (..)        => unimplemented!(),
-                    Stmt::Snaz(..)        => unimplemented!(),
                     Stmt::Sne ([_, a, b]) => Stmt::Breq ([a, b, c]),
                     Stmt::Snez([_, a])    => Stmt::Breqz([a,    c]),
                     _ => {
@@ -279,21 +656,33 @@ impl Translator {
 
         let myps::ast::Block { branch, items } = block;
         let mut lines = match branch {
-            Branch::Program => self.translate_items(items).unwrap(),
+            Branch::Program => {
+                let mut lines = self.translate_items(items)?;
+                self.scope_functions(&mut lines)?;
+                lines
+            }
             Branch::Loop => {
-                let mut lines = self.translate_items(items).unwrap();
+                let (mut lines, loop_tags) = self.translate_loop_body(items)?;
                 let line_rel = LineRel((-(lines.len() as f64)).into());
                 let stmt_jr = Stmt::Jr([line_rel.into()]);
                 lines.push(Line::new_no_comment(stmt_jr));
+                // A continue jumps back to the start
+                if loop_tags.continues {
+                    shift_scopes(&mut lines, 1);
+                    lines.insert(0, tag_line(next_loop_tag(loop_tags.id)));
+                }
+                if loop_tags.breaks {
+                    lines.push(tag_line(end_loop_tag(loop_tags.id)));
+                }
                 lines
             }
             Branch::If { cond, chain_id_opt } => {
                 // Translate condition expression to statements
-                let (cond_num, cond_stmts) = self.translate_expr(None, cond).unwrap();
+                let (cond_num, cond_stmts) = self.translate_expr(None, cond)?;
                 // Transform last condition statement to branch
                 let mut cond_stmts = transform_condition(self, cond_stmts, cond_num);
                 // Translate body items to lines
-                let mut body_lines = self.translate_items(items).unwrap();
+                let mut body_lines = self.translate_items(items)?;
                 // Shift body hints
                 shift_scopes(&mut body_lines, cond_stmts.len());
                 // If part of chain, add jump to post if-elif-else chain tag
@@ -322,11 +711,11 @@ impl Translator {
                 end_chain,
             } => {
                 // Translate condition expression to statements
-                let (cond_num, cond_stmts) = self.translate_expr(None, cond).unwrap();
+                let (cond_num, cond_stmts) = self.translate_expr(None, cond)?;
                 // Transform last condition statement to branch
                 let mut cond_stmts = transform_condition(self, cond_stmts, cond_num);
                 // Translate body items to lines
-                let mut body_lines = self.translate_items(items).unwrap();
+                let mut body_lines = self.translate_items(items)?;
                 // Shift body hints
                 shift_scopes(&mut body_lines, cond_stmts.len());
                 // Push if-elif-else chain line
@@ -356,19 +745,19 @@ impl Translator {
             }
             Branch::Else { chain_id } => {
                 // Translate body items to lines
-                let mut lines = self.translate_items(items).unwrap();
+                let mut lines = self.translate_items(items)?;
                 // Push post if-elif-else chain tag
-                let chain_stmt = Stmt::Tag([tag(chain_id).into()]);
+                let chain_stmt = Stmt::Tag([tag_string(chain_id).into()]);
                 lines.push(Line::new_no_comment(chain_stmt));
                 lines
             }
             Branch::While { cond } => {
                 // Translate condition expression to statements
-                let (cond_num, cond_stmts) = self.translate_expr(None, cond).unwrap();
+                let (cond_num, cond_stmts) = self.translate_expr(None, cond)?;
                 // Transform last condition statement to branch
                 let mut cond_stmts = transform_condition(self, cond_stmts, cond_num);
                 // Translate body items to lines
-                let mut body_lines = self.translate_items(items).unwrap();
+                let (mut body_lines, loop_tags) = self.translate_loop_body(items)?;
                 // Shift body hints
                 shift_scopes(&mut body_lines, cond_stmts.len());
                 // Push backwards jump
@@ -386,10 +775,18 @@ impl Translator {
                     .map(Line::new_no_comment)
                     .collect::<Vec<_>>();
                 // Construct lines
-                let lines = cond_lines
+                let mut lines = cond_lines
                     .into_iter()
                     .chain(body_lines.into_iter())
-                    .collect();
+                    .collect::<Vec<_>>();
+                // A continue jumps back to the condition
+                if loop_tags.continues {
+                    shift_scopes(&mut lines, 1);
+                    lines.insert(0, tag_line(next_loop_tag(loop_tags.id)));
+                }
+                if loop_tags.breaks {
+                    lines.push(tag_line(end_loop_tag(loop_tags.id)));
+                }
                 lines
             }
             Branch::For(Var { key, .. }, s, e, step) => {
@@ -402,7 +799,7 @@ impl Translator {
                 // Translate loop index initialization to statements
                 let i_lines = {
                     // Translate index start expression to statements
-                    let (s_num, mut s_stmts) = self.translate_expr(Some(i_reg_base), s).unwrap();
+                    let (s_num, mut s_stmts) = self.translate_expr(Some(i_reg_base), s)?;
                     // If start expression is a literal number then add a Move statement
                     if matches!(s_num, Num::Lit(_)) {
                         let lhs = Arg::Reg(i_reg_base.into());
@@ -416,7 +813,7 @@ impl Translator {
                 };
                 // Translate loop index end expression to statements
                 let (e_num, e_lines) = {
-                    let (e_num, e_stmts) = self.translate_expr(None, e).unwrap();
+                    let (e_num, e_stmts) = self.translate_expr(None, e)?;
                     let e_lines = e_stmts
                         .into_iter()
                         .map(Line::new_no_comment)
@@ -425,7 +822,7 @@ impl Translator {
                 };
                 // Translate step expression to statements
                 let (step_num, step_lines) = {
-                    let (step_num, step_stmts) = self.translate_expr(None, step).unwrap();
+                    let (step_num, step_stmts) = self.translate_expr(None, step)?;
                     let step_lines = step_stmts
                         .into_iter()
                         .map(Line::new_no_comment)
@@ -440,11 +837,15 @@ impl Translator {
                     vec![Stmt::Brge([a, b, c])]
                 };
                 // Translate body items to lines
-                let mut body_lines = self.translate_items(items).unwrap();
+                let (mut body_lines, loop_tags) = self.translate_loop_body(items)?;
                 // Shift body hints
                 let shift_by =
                     i_lines.len() + e_lines.len() + step_lines.len() + cond_stmts.len() + 1;
                 shift_scopes(&mut body_lines, shift_by);
+                // A continue jumps to the increment
+                if loop_tags.continues {
+                    body_lines.push(tag_line(next_loop_tag(loop_tags.id)));
+                }
                 // Push increment statement
                 body_lines.push({
                     let r = Arg::Reg(Reg::Base(i_reg_base));
@@ -469,18 +870,110 @@ impl Translator {
                     .map(Line::new_no_comment)
                     .collect::<Vec<_>>();
                 // Collect lines
-                let lines = i_lines
+                let mut lines = i_lines
                     .into_iter()
                     .chain(e_lines.into_iter())
                     .chain(step_lines.into_iter())
                     .chain(cond_lines.into_iter())
                     .chain(body_lines.into_iter())
                     .collect::<Vec<_>>();
+                if loop_tags.breaks {
+                    lines.push(tag_line(end_loop_tag(loop_tags.id)));
+                }
                 lines
             }
+            Branch::Fn { name, params } => {
+                if self.functions.contains_key(&name) || self.macros.contains_key(&name) {
+                    return Err(MipsError::unsupported("Redefining a function").into());
+                }
+                let start = self.next_index;
+                // The body can only use the devices, constants and fixed variables of the program,
+                // along with its parameters
+                let aliases = self.aliases.clone();
+                self.aliases.map.retain(|_, alias| match alias {
+                    Alias::Reg(reg_base @ RegBase::Lit(..)) => reg_base.fixed(),
+                    _ => true,
+                });
+                let params = params
+                    .into_iter()
+                    .map(|Var { key, .. }| {
+                        let reg_base = self.next_reg_base(0, false);
+                        self.aliases.insert(key, Alias::Reg(reg_base));
+                        reg_base
+                    })
+                    .collect();
+                let ret = self.next_reg_base(0, false);
+                let function = Function {
+                    params,
+                    ret,
+                    returns: false,
+                    indices: start..start,
+                    callees: BTreeSet::new(),
+                };
+                // Return at the end of the body, unless it already does
+                let ends_with_return = matches!(
+                    items.iter().rev().find(|item| item.is_not_empty()),
+                    Some(myps::ast::Item::Stmt(myps::ast::Stmt::Return(..), _))
+                );
+                self.function_opt = Some((name.clone(), function));
+                let body_result = self.translate_items(items);
+                let (_, mut function) = self.function_opt.take().unwrap();
+                self.aliases = aliases;
+                let mut body_lines = body_result?;
+                function.indices = start..self.next_index;
+                self.functions.insert(name.clone(), function);
+                if !ends_with_return {
+                    let stmt = Stmt::J([Arg::LineAbs(LineAbs(RegBase::RA.into()))]);
+                    body_lines.push(Line::new_no_comment(stmt));
+                }
+                // Skip over the body (to the tag of the function and then past the body)
+                shift_scopes(&mut body_lines, 2);
+                let jump_by = (body_lines.len() + 2) as i64;
+                let stmt_jr = Stmt::Jr([Arg::LineRel(jump_by.into())]);
+                once(Line::new_no_comment(stmt_jr))
+                    .chain(once(tag_line(name)))
+                    .chain(body_lines)
+                    .collect()
+            }
+            Branch::Macro { name, params } => {
+                if self.functions.contains_key(&name) || self.macros.contains_key(&name) {
+                    return Err(MipsError::unsupported("Redefining a function").into());
+                }
+                // The body is only translated where the macro is called, but its source lines are
+                // still counted
+                let line = self.line;
+                self.line += items.iter().map(|item| item.iter().count()).sum::<usize>();
+                let params = params.into_iter().map(|Var { key, .. }| key).collect();
+                let mac = Macro {
+                    params,
+                    items,
+                    line,
+                    import_opt: self.import_opt.clone(),
+                };
+                self.macros.insert(name, mac);
+                Vec::new()
+            }
+            Branch::Import { path, source } => {
+                // The imported items are counted as lines of their own file, and their errors
+                // located there
+                let line = self.line;
+                self.line = 0;
+                let import_opt = self.import_opt.replace((path.clone(), source.clone()));
+                let lines_result = self.translate_items(items);
+                self.line = line;
+                self.import_opt = import_opt;
+                let mut lines = lines_result.map_err(|e| e.with_source(&source).in_file(&path))?;
+                // Lines are mapped to the import in the source of the program
+                for line in lines.iter_mut() {
+                    line.source_line = None;
+                }
+                // The items are merged into the importing block, so their fixed variables aren't
+                // scoped to them
+                return Ok(lines);
+            }
             Branch::Tag(tag) => {
                 let tag_stmt = Stmt::Tag([Arg::String(tag)]);
-                let mut body_lines = self.translate_items(items).unwrap();
+                let mut body_lines = self.translate_items(items)?;
                 shift_scopes(&mut body_lines, 1);
                 let lines = once(tag_stmt)
                     .map(Line::new_no_comment)
@@ -504,8 +997,8 @@ impl Translator {
         Ok(lines)
     }
 
-    fn translate_stmt(&mut self, stmt: myps::ast::Stmt) -> MipsResult<Vec<mips::ast::Stmt>> {
-        use mips::ast::{Arg, Stmt, Num};
+    fn translate_stmt(&mut self, stmt: myps::ast::Stmt) -> MypsResult<Vec<mips::ast::Stmt>> {
+        use mips::ast::{Arg, LineAbs, Num, Stmt};
         use myps::ast::{Lv, Var};
         use std::iter::once;
 
@@ -523,23 +1016,92 @@ impl Translator {
                 Ok(stmts)
             }
             myps::ast::Stmt::Asn(lv, rv) => {
-                let (stmts, alias_pair) = self.translate_assignment(lv, rv).unwrap();
+                let (stmts, alias_pair) = self.translate_assignment(lv, rv)?;
                 if let Some((key, alias)) = alias_pair {
                     self.aliases.insert(key, alias);
                 }
                 Ok(stmts)
             }
+            myps::ast::Stmt::LvRvAsn(lvs, rvs) => self.translate_lv_rv_assignment(lvs, rvs),
             myps::ast::Stmt::SelfAsn {
                 op,
-                lhs: Var { key, .. },
+                lhs,
+                mode_opt,
                 rhs,
             } => {
-                use myps::ast::BinaryOp;
+                use myps::ast::{BinaryOp, Lv};
 
-                let reg_base = self.try_get_reg(&key).unwrap();
+                let (b_num, mut stmts) = self.translate_expr(None, rhs)?;
+                // The parameter of the devices of a hash is batch-loaded with the given mode
+                let batch_mode = mode_opt.ok_or_else(MypsError::batch_mode_missing);
+                // A device parameter is loaded into a register, and stored after the operation
+                let (reg_base, store_opt) = match lhs {
+                    Lv::Var(Var { key, .. }) => (self.try_get_reg(&key)?, None),
+                    Lv::DevParam { dev, param } => {
+                        let reg_base = self.next_reg_base(0, false);
+                        let (dev_base, dev_stmts) = self.translate_dev(dev)?;
+                        let (d, p) = (Arg::from(dev_base), Arg::from(param));
+                        stmts.extend(dev_stmts);
+                        stmts.push(Stmt::L([reg_base.into(), d.clone(), p.clone()]));
+                        let s_stmt = Stmt::S([d, p, Num::from(reg_base).into()]);
+                        (reg_base, Some(s_stmt))
+                    }
+                    Lv::DevSlot { dev, slot, param } => {
+                        let reg_base = self.next_reg_base(0, false);
+                        let (dev_base, dev_stmts) = self.translate_dev(dev)?;
+                        let (slot, slot_stmts) = self.translate_num(None, slot)?;
+                        let (d, n, p) = (Arg::from(dev_base), Arg::from(slot), Arg::from(param));
+                        stmts.extend(dev_stmts);
+                        stmts.extend(slot_stmts);
+                        let ls_stmt = Stmt::Ls([reg_base.into(), d.clone(), n.clone(), p.clone()]);
+                        stmts.push(ls_stmt);
+                        let ss_stmt = Stmt::Ss([d, n, p, Num::from(reg_base).into()]);
+                        (reg_base, Some(ss_stmt))
+                    }
+                    Lv::NetParam { hash, param } => {
+                        let reg_base = self.next_reg_base(0, false);
+                        let (hash, hash_stmts) = self.translate_num(None, hash)?;
+                        let (mode, mode_stmts) = self.translate_mode(batch_mode?)?;
+                        let (h, p) = (Arg::from(hash), Arg::from(param));
+                        stmts.extend(hash_stmts);
+                        stmts.extend(mode_stmts);
+                        stmts.push(Stmt::Lb([reg_base.into(), h.clone(), p.clone(), mode]));
+                        let sb_stmt = Stmt::Sb([h, p, Num::from(reg_base).into()]);
+                        (reg_base, Some(sb_stmt))
+                    }
+                    Lv::NetSlot { hash, slot, param } => {
+                        let reg_base = self.next_reg_base(0, false);
+                        let (hash, hash_stmts) = self.translate_num(None, hash)?;
+                        let (slot, slot_stmts) = self.translate_num(None, slot)?;
+                        let (mode, mode_stmts) = self.translate_mode(batch_mode?)?;
+                        let (h, n, p) = (Arg::from(hash), Arg::from(slot), Arg::from(param));
+                        stmts.extend(hash_stmts);
+                        stmts.extend(slot_stmts);
+                        stmts.extend(mode_stmts);
+                        let r = reg_base.into();
+                        stmts.push(Stmt::Lbs([r, h.clone(), n.clone(), p.clone(), mode]));
+                        let sbs_stmt = Stmt::Sbs([h, n, p, Num::from(reg_base).into()]);
+                        (reg_base, Some(sbs_stmt))
+                    }
+                    Lv::NetName { hash, name, param } => {
+                        let reg_base = self.next_reg_base(0, false);
+                        let (hash, hash_stmts) = self.translate_num(None, hash)?;
+                        let name_hash = Num::Lit(constants::hash(&name) as f64);
+                        let (mode, mode_stmts) = self.translate_mode(batch_mode?)?;
+                        let (h, n, p) = (Arg::from(hash), Arg::from(name_hash), Arg::from(param));
+                        stmts.extend(hash_stmts);
+                        stmts.extend(mode_stmts);
+                        let r = reg_base.into();
+                        stmts.push(Stmt::Lbn([r, h.clone(), n.clone(), p.clone(), mode]));
+                        let sbn_stmt = Stmt::Sbn([h, n, p, Num::from(reg_base).into()]);
+                        (reg_base, Some(sbn_stmt))
+                    }
+                    Lv::Def(..) => {
+                        return Err(MipsError::unsupported("Assigning to a constant").into())
+                    }
+                };
                 let r = reg_base.into();
                 let a = Num::Reg(reg_base).into();
-                let (b_num, b_stmts) = self.translate_expr(None, rhs).unwrap();
                 let b = b_num.into();
                 let stmt = match op {
                     BinaryOp::Add => Stmt::Add([r, a, b]),
@@ -549,81 +1111,216 @@ impl Translator {
                     BinaryOp::Rem => Stmt::Mod([r, a, b]),
                     _ => unreachable!(),
                 };
-                let stmts = b_stmts.into_iter().chain(once(stmt)).collect();
+                stmts.push(stmt);
+                stmts.extend(store_opt);
                 Ok(stmts)
             }
             myps::ast::Stmt::Mips(mut stmt) => {
                 for arg in stmt.iter_args_mut() {
                     match arg {
                         Arg::Dev(..) | Arg::Reg(..) | Arg::Num(..) => {
-                            *arg = arg.clone().reduce(&self.aliases).unwrap();
+                            *arg = arg.clone().reduce(&self.aliases)?;
                         }
                         _ => {}
                     }
                 }
                 Ok(vec![stmt])
             }
+            myps::ast::Stmt::Call(call) => {
+                let (_, stmts) = self.translate_call(None, call, false)?;
+                Ok(stmts)
+            }
+            myps::ast::Stmt::Return(expr_opt) => {
+                // A return within a macro jumps past its expanded body, and otherwise back from a
+                // function
+                let (ret, target) = if let Some(expansion) = self.expansions.last_mut() {
+                    expansion.jumps += 1;
+                    let tag = end_macro_tag(expansion.id);
+                    (expansion.ret, LineAbs(tag.into()))
+                } else {
+                    let error = || MipsError::unsupported("'return' outside of a function");
+                    let (_, function) = self.function_opt.as_mut().ok_or_else(error)?;
+                    function.returns |= expr_opt.is_some();
+                    (function.ret, LineAbs(RegBase::RA.into()))
+                };
+                let mut stmts = match expr_opt {
+                    Some(expr) => {
+                        let (num, mut stmts) = self.translate_expr(Some(ret), expr)?;
+                        if stmts.is_empty() {
+                            stmts.push(Stmt::Move([ret.into(), num.into()]));
+                        }
+                        stmts
+                    }
+                    None => Vec::new(),
+                };
+                stmts.push(Stmt::J([Arg::LineAbs(target)]));
+                Ok(stmts)
+            }
+            myps::ast::Stmt::Break => {
+                let loop_tags = self
+                    .loops
+                    .last_mut()
+                    .ok_or_else(|| MipsError::unsupported("'break' outside of a loop"))?;
+                loop_tags.breaks = true;
+                let tag = end_loop_tag(loop_tags.id);
+                Ok(vec![Stmt::J([Arg::LineAbs(LineAbs(tag.into()))])])
+            }
+            myps::ast::Stmt::Continue => {
+                let loop_tags = self
+                    .loops
+                    .last_mut()
+                    .ok_or_else(|| MipsError::unsupported("'continue' outside of a loop"))?;
+                loop_tags.continues = true;
+                let tag = next_loop_tag(loop_tags.id);
+                Ok(vec![Stmt::J([Arg::LineAbs(LineAbs(tag.into()))])])
+            }
+            myps::ast::Stmt::Import(..) => unreachable!("imports are replaced by the lexer"),
             myps::ast::Stmt::Empty => Ok(vec![Stmt::Empty([])]),
         }
     }
 
+    // Write a number to the parameter of a device
+    fn translate_s(
+        &mut self,
+        num: mips::ast::Num,
+        dev: myps::ast::Dev,
+        param: LogicType,
+    ) -> MypsResult<Vec<mips::ast::Stmt>> {
+        use mips::ast::Stmt;
+
+        let (dev, mut stmts) = self.translate_dev(dev)?;
+        let d = dev.into();
+        let p = param.into();
+        let r = num.into();
+        let stmt = Stmt::S([d, p, r]);
+        stmts.push(stmt);
+        Ok(stmts)
+    }
+
+    // Batch-write a number to the parameter of the devices of a hash
+    fn translate_sb(
+        &mut self,
+        num: mips::ast::Num,
+        hash: myps::ast::Num,
+        param: LogicType,
+    ) -> MypsResult<Vec<mips::ast::Stmt>> {
+        use mips::ast::Stmt;
+
+        let (hash, mut stmts) = self.translate_num(None, hash)?;
+        let h = hash.into();
+        let p = param.into();
+        let r = num.into();
+        let stmt = Stmt::Sb([h, p, r]);
+        stmts.push(stmt);
+        Ok(stmts)
+    }
+
+    // Write a number to the parameter of a slot of a device
+    fn translate_ss(
+        &mut self,
+        num: mips::ast::Num,
+        dev: myps::ast::Dev,
+        slot: myps::ast::Num,
+        param: SlotLogicType,
+    ) -> MypsResult<Vec<mips::ast::Stmt>> {
+        use mips::ast::Stmt;
+
+        let (dev, mut stmts) = self.translate_dev(dev)?;
+        let (slot, slot_stmts) = self.translate_num(None, slot)?;
+        stmts.extend(slot_stmts);
+        let d = dev.into();
+        let n = slot.into();
+        let p = param.into();
+        let r = num.into();
+        let stmt = Stmt::Ss([d, n, p, r]);
+        stmts.push(stmt);
+        Ok(stmts)
+    }
+
+    // Batch-write a number to the parameter of a slot of the devices of a hash
+    fn translate_sbs(
+        &mut self,
+        num: mips::ast::Num,
+        hash: myps::ast::Num,
+        slot: myps::ast::Num,
+        param: SlotLogicType,
+    ) -> MypsResult<Vec<mips::ast::Stmt>> {
+        use mips::ast::Stmt;
+
+        let (hash, mut stmts) = self.translate_num(None, hash)?;
+        let (slot, slot_stmts) = self.translate_num(None, slot)?;
+        stmts.extend(slot_stmts);
+        let h = hash.into();
+        let n = slot.into();
+        let p = param.into();
+        let r = num.into();
+        let stmt = Stmt::Sbs([h, n, p, r]);
+        stmts.push(stmt);
+        Ok(stmts)
+    }
+
+    // Batch-write a number to the parameter of the devices of a hash and a name
+    fn translate_sbn(
+        &mut self,
+        num: mips::ast::Num,
+        hash: myps::ast::Num,
+        name: &str,
+        param: LogicType,
+    ) -> MypsResult<Vec<mips::ast::Stmt>> {
+        use mips::ast::{Num, Stmt};
+
+        let (hash, mut stmts) = self.translate_num(None, hash)?;
+        let h = hash.into();
+        let n = Num::Lit(constants::hash(name) as f64).into();
+        let p = param.into();
+        let r = num.into();
+        let stmt = Stmt::Sbn([h, n, p, r]);
+        stmts.push(stmt);
+        Ok(stmts)
+    }
+
     fn translate_assignment(
         &mut self,
         lhs: myps::ast::Lv,
         rhs: myps::ast::Rv,
-    ) -> MipsResult<(Vec<mips::ast::Stmt>, Option<(String, Alias)>)> {
+    ) -> MypsResult<Assignment> {
         use mips::ast::{Arg, Dev, Num, Reg, Stmt};
         use myps::ast::{Lv, Rv, Var};
 
-        fn translate_s(
-            translator: &mut Translator,
-            num: mips::ast::Num,
-            dev: myps::ast::Dev,
-            param: String,
-        ) -> MipsResult<Vec<Stmt>> {
-            let (dev, mut stmts) = translator.translate_dev(dev).unwrap();
-            let d = dev.into();
-            let p = param.into();
-            let r = num.into();
-            let stmt = Stmt::S([d, p, r]);
-            stmts.push(stmt);
-            Ok(stmts)
-        }
-
-        fn translate_sb(
-            translator: &mut Translator,
-            num: mips::ast::Num,
-            hash: myps::ast::Num,
-            param: String,
-        ) -> MipsResult<Vec<Stmt>> {
-            let (hash, mut stmts) = translator.translate_num(None, hash).unwrap();
-            let h = hash.into();
-            let p = param.into();
-            let r = num.into();
-            let stmt = Stmt::Sb([h, p, r]);
-            stmts.push(stmt);
-            Ok(stmts)
-        }
-
         match rhs {
             Rv::Expr(expr) => match lhs {
                 Lv::DevParam { dev, param } => {
-                    let (num, mut stmts) = self.translate_expr(None, expr).unwrap();
-                    stmts.extend(translate_s(self, num, dev, param).unwrap());
+                    let (num, mut stmts) = self.translate_expr(None, expr)?;
+                    stmts.extend(self.translate_s(num, dev, param)?);
+                    Ok((stmts, None))
+                }
+                Lv::DevSlot { dev, slot, param } => {
+                    let (num, mut stmts) = self.translate_expr(None, expr)?;
+                    stmts.extend(self.translate_ss(num, dev, slot, param)?);
                     Ok((stmts, None))
                 }
                 Lv::NetParam { hash, param } => {
-                    let (num, mut stmts) = self.translate_expr(None, expr).unwrap();
-                    stmts.extend(translate_sb(self, num, hash, param).unwrap());
+                    let (num, mut stmts) = self.translate_expr(None, expr)?;
+                    stmts.extend(self.translate_sb(num, hash, param)?);
+                    Ok((stmts, None))
+                }
+                Lv::NetSlot { hash, slot, param } => {
+                    let (num, mut stmts) = self.translate_expr(None, expr)?;
+                    stmts.extend(self.translate_sbs(num, hash, slot, param)?);
+                    Ok((stmts, None))
+                }
+                Lv::NetName { hash, name, param } => {
+                    let (num, mut stmts) = self.translate_expr(None, expr)?;
+                    stmts.extend(self.translate_sbn(num, hash, &name, param)?);
                     Ok((stmts, None))
                 }
                 Lv::Var(Var {
                     key: lv_key,
                     fixed: lv_fixed,
                 }) => {
-                    let lv_reg_base = self.try_get_reg_or_next(&lv_key, 0, lv_fixed).unwrap();
+                    let lv_reg_base = self.try_get_reg_or_next(&lv_key, 0, lv_fixed)?;
                     let fixed = lv_fixed || lv_reg_base.fixed();
-                    let (num, mut stmts) = self.translate_expr(Some(lv_reg_base), expr).unwrap();
+                    let (num, mut stmts) = self.translate_expr(Some(lv_reg_base), expr)?;
                     if stmts.is_empty() {
                         let r = lv_reg_base.into();
                         let a = num.into();
@@ -634,101 +1331,208 @@ impl Translator {
                     Ok((stmts, Some(alias_pair)))
                 }
                 Lv::Def(lv_key) => {
-                    let (num, _) = self.translate_expr(None, expr).unwrap();
-                    match num {
-                        Num::Lit(n) => {
-                            let alias = Alias::Num(n);
-                            let alias_pair = (lv_key, alias);
-                            Ok((Vec::new(), Some(alias_pair)))
-                        }
-                        Num::Alias(rv_key) => {
-                            let rv_alias = self.try_get_alias(&rv_key).unwrap();
-                            unimplemented!();
-                        }
-                        Num::Reg(_) => unreachable!(),
-                    }
+                    let n = self.eval_const_expr(&lv_key, &expr)?;
+                    let alias_pair = (lv_key, Alias::Num(n));
+                    Ok((Vec::new(), Some(alias_pair)))
                 }
             },
-            Rv::Var(Var {
-                key: rv_key,
-                fixed: rv_fixed,
-            }) => {
-                let rv_alias = self.try_get_alias(&rv_key).unwrap().clone();
-                match lhs {
-                    Lv::DevParam { dev, param } => {
-                        let num = Num::try_from(&rv_alias).unwrap();
-                        let stmts = translate_s(self, num, dev, param).unwrap();
-                        Ok((stmts, None))
-                    }
-                    Lv::NetParam { hash, param } => {
-                        let num = Num::try_from(&rv_alias).unwrap();
-                        let stmts = translate_sb(self, num, hash, param).unwrap();
-                        Ok((stmts, None))
-                    }
-                    Lv::Var(Var {
-                        key: lv_key,
-                        fixed: lv_fixed,
-                    }) => match rv_alias {
-                        Alias::Num(n) => {
-                            let lv_reg_base =
-                                self.try_get_reg_or_next(&lv_key, 0, lv_fixed).unwrap();
-                            let stmt =
-                                Stmt::Move([Arg::Reg(lv_reg_base.into()), Arg::Num(n.into())]);
-                            let stmts = vec![stmt];
-                            let alias = lv_reg_base.into();
-                            let alias_pair = (lv_key, alias);
-                            Ok((stmts, Some(alias_pair)))
-                        }
-                        Alias::Dev(..) => {
-                            unimplemented!();
-                        }
-                        Alias::Reg(rv_reg_base) => {
-                            let lv_reg_base =
-                                self.try_get_reg_or_next(&lv_key, 0, lv_fixed).unwrap();
-                            let stmt = Stmt::Move([
-                                Arg::Reg(lv_reg_base.into()),
-                                Arg::Num(rv_reg_base.into()),
-                            ]);
-                            let stmts = vec![stmt];
-                            let alias = lv_reg_base.into();
-                            let alias_pair = (lv_key, alias);
-                            Ok((stmts, Some(alias_pair)))
-                        }
-                    },
-                    Lv::Def(_) => {
-                        unreachable!();
-                    }
-                }
+            Rv::Var(Var { key: rv_key, .. }) => {
+                let rv_alias = self.try_get_alias(&rv_key)?.clone();
+                self.translate_alias_assignment(lhs, rv_alias, &rv_key)
             }
             Rv::Dev(dev) => {
-                let (dev_base, stmts) = self.translate_dev(dev).unwrap();
+                let (dev_base, stmts) = self.translate_dev(dev)?;
                 if let Lv::Var(Var { key: lv_key, .. }) = lhs {
                     let stmts = vec![Stmt::Alias([lv_key.clone().into(), dev_base.into()])];
                     let alias_pair = (lv_key, dev_base.into());
                     Ok((stmts, Some(alias_pair)))
                 } else {
-                    unreachable!();
+                    Err(MipsError::unsupported("Assigning a device to a device parameter").into())
+                }
+            }
+        }
+    }
+
+    // Assign the value of an alias (of the variable `rv_key`) to an l-value
+    fn translate_alias_assignment(
+        &mut self,
+        lhs: myps::ast::Lv,
+        rv_alias: Alias,
+        rv_key: &str,
+    ) -> MypsResult<Assignment> {
+        use mips::ast::{Arg, Num, Stmt};
+        use myps::ast::{Lv, Var};
+
+        match lhs {
+            Lv::DevParam { dev, param } => {
+                let num = Num::try_from(&rv_alias)?;
+                let stmts = self.translate_s(num, dev, param)?;
+                Ok((stmts, None))
+            }
+            Lv::DevSlot { dev, slot, param } => {
+                let num = Num::try_from(&rv_alias)?;
+                let stmts = self.translate_ss(num, dev, slot, param)?;
+                Ok((stmts, None))
+            }
+            Lv::NetParam { hash, param } => {
+                let num = Num::try_from(&rv_alias)?;
+                let stmts = self.translate_sb(num, hash, param)?;
+                Ok((stmts, None))
+            }
+            Lv::NetSlot { hash, slot, param } => {
+                let num = Num::try_from(&rv_alias)?;
+                let stmts = self.translate_sbs(num, hash, slot, param)?;
+                Ok((stmts, None))
+            }
+            Lv::NetName { hash, name, param } => {
+                let num = Num::try_from(&rv_alias)?;
+                let stmts = self.translate_sbn(num, hash, &name, param)?;
+                Ok((stmts, None))
+            }
+            Lv::Var(Var {
+                key: lv_key,
+                fixed: lv_fixed,
+            }) => match rv_alias {
+                Alias::Num(n) => {
+                    let lv_reg_base = self.try_get_reg_or_next(&lv_key, 0, lv_fixed)?;
+                    let stmt = Stmt::Move([Arg::Reg(lv_reg_base.into()), Arg::Num(n.into())]);
+                    let stmts = vec![stmt];
+                    let alias = lv_reg_base.into();
+                    let alias_pair = (lv_key, alias);
+                    Ok((stmts, Some(alias_pair)))
+                }
+                Alias::Dev(..) => {
+                    Err(MipsError::unsupported("Assigning a device variable to a variable").into())
+                }
+                Alias::Reg(rv_reg_base) => {
+                    let lv_reg_base = self.try_get_reg_or_next(&lv_key, 0, lv_fixed)?;
+                    let stmt =
+                        Stmt::Move([Arg::Reg(lv_reg_base.into()), Arg::Num(rv_reg_base.into())]);
+                    let stmts = vec![stmt];
+                    let alias = lv_reg_base.into();
+                    let alias_pair = (lv_key, alias);
+                    Ok((stmts, Some(alias_pair)))
+                }
+            },
+            Lv::Def(lv_key) => match rv_alias {
+                Alias::Num(n) => Ok((Vec::new(), Some((lv_key, Alias::Num(n))))),
+                _ => {
+                    let what = format!("the variable '{}'", rv_key);
+                    Err(MypsError::def_not_const(&lv_key, &what))
+                }
+            },
+        }
+    }
+
+    // Translate a parallel assignment, in which every r-value is evaluated before any l-value is
+    // assigned (so that e.g. `a, b = b, a` swaps)
+    fn translate_lv_rv_assignment(
+        &mut self,
+        lvs: Vec<myps::ast::Lv>,
+        rvs: Vec<myps::ast::Rv>,
+    ) -> MypsResult<Vec<mips::ast::Stmt>> {
+        use mips::ast::{Num, Stmt};
+        use myps::ast::{Lv, Rv, Var};
+
+        // The registers of the variables assigned to
+        let lv_indices = lvs
+            .iter()
+            .map(|lv| match lv {
+                Lv::Var(Var { key, .. }) => self.get_reg(key).map(|reg_base| reg_base.index()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Evaluate the r-values into aliases (of temporaries where needed)
+        let mut stmts = Vec::new();
+        let mut rv_aliases = Vec::new();
+        for (i, (lv, rv)) in lvs.iter().zip(rvs).enumerate() {
+            let (rv_key, rv_alias) = match (lv, rv) {
+                (Lv::Def(lv_key), Rv::Expr(expr)) => {
+                    let n = self.eval_const_expr(lv_key, &expr)?;
+                    (String::new(), Alias::Num(n))
+                }
+                (Lv::Def(lv_key), Rv::Dev(..)) => {
+                    return Err(MypsError::def_not_const(lv_key, "a device"));
                 }
+                (_, Rv::Expr(expr)) => {
+                    let (num, num_stmts) = self.translate_expr(None, expr)?;
+                    stmts.extend(num_stmts);
+                    let alias = match num {
+                        Num::Lit(n) => Alias::Num(n),
+                        Num::Reg(reg_base) => Alias::Reg(reg_base),
+                        Num::Alias(key) => self.try_get_alias(&key)?.clone(),
+                    };
+                    (String::new(), alias)
+                }
+                (_, Rv::Var(Var { key, .. })) => {
+                    let alias = self.try_get_alias(&key)?.clone();
+                    (key, alias)
+                }
+                (_, Rv::Dev(dev)) => {
+                    let (dev_base, dev_stmts) = self.translate_dev(dev)?;
+                    stmts.extend(dev_stmts);
+                    (String::new(), Alias::Dev(dev_base))
+                }
+            };
+            // A variable assigned to by a previous l-value is copied, so that it's read before it's
+            // assigned
+            let rv_alias = match rv_alias {
+                Alias::Reg(reg_base) if lv_indices[..i].contains(&Some(reg_base.index())) => {
+                    let copy = self.next_reg_base(0, false);
+                    stmts.push(Stmt::Move([copy.into(), Num::from(reg_base).into()]));
+                    Alias::Reg(copy)
+                }
+                _ => rv_alias,
+            };
+            rv_aliases.push((rv_key, rv_alias));
+        }
+
+        // Assign the l-values
+        for (lv, (rv_key, rv_alias)) in lvs.into_iter().zip(rv_aliases) {
+            let (lv_stmts, alias_pair) = match (lv, rv_alias) {
+                (Lv::Var(Var { key: lv_key, .. }), Alias::Dev(dev_base)) => {
+                    let stmt = Stmt::Alias([lv_key.clone().into(), dev_base.into()]);
+                    (vec![stmt], Some((lv_key, dev_base.into())))
+                }
+                (lv, rv_alias) => self.translate_alias_assignment(lv, rv_alias, &rv_key)?,
+            };
+            stmts.extend(lv_stmts);
+            if let Some((key, alias)) = alias_pair {
+                self.aliases.insert(key, alias);
             }
         }
+        Ok(stmts)
     }
 
     fn translate_expr(
         &mut self,
         reg_base_opt: Option<mips::ast::RegBase>,
         expr: myps::ast::Expr,
-    ) -> MipsResult<(mips::ast::Num, Vec<mips::ast::Stmt>)> {
+    ) -> MypsResult<(mips::ast::Num, Vec<mips::ast::Stmt>)> {
         use mips::ast::{Arg, Num, Reg, Stmt};
         use myps::ast::Expr;
         use std::iter::once;
 
+        // An operation of literals and constants (e.g. `a + b` of defs) is folded once its
+        // operands are translated, giving back the registers taken for it
+        let next_index = self.next_index;
+        macro_rules! fold {
+            ($n:expr) => {{
+                self.next_index = next_index;
+                return Ok((Num::Lit($n), Vec::new()));
+            }};
+        }
         match expr {
             Expr::Num(num) => self.translate_num(reg_base_opt, num),
             Expr::Unary { op, rhs } => {
                 use myps::ast::UnaryOp;
 
                 let reg_base = self.unwrap_reg_base(reg_base_opt);
-                let (b_num, b_stmts) = self.translate_num(None, rhs).unwrap();
+                let (b_num, b_stmts) = self.translate_num(None, rhs)?;
+                if let Some(b) = lit_value(&b_num, &b_stmts) {
+                    fold!(op.apply(b));
+                }
                 let r = reg_base.into();
                 let b = b_num.into();
                 let op_stmt = match op {
@@ -740,6 +1544,7 @@ impl Translator {
                         let a = b.clone();
                         Stmt::Nor([r, a, b])
                     }
+                    UnaryOp::BitNot => Stmt::Not([r, b]),
                 };
                 let stmts = b_stmts.into_iter().chain(once(op_stmt)).collect();
                 Ok((reg_base.into(), stmts))
@@ -765,8 +1570,12 @@ impl Translator {
                 }
 
                 let reg_base = self.unwrap_reg_base(reg_base_opt);
-                let (a_num, a_stmts) = self.translate_num(None, lhs).unwrap();
-                let (b_num, b_stmts) = self.translate_num(None, rhs).unwrap();
+                let (a_num, a_stmts) = self.translate_num(None, lhs)?;
+                let (b_num, b_stmts) = self.translate_num(None, rhs)?;
+                let a_lit = lit_value(&a_num, &a_stmts);
+                if let (Some(a), Some(b)) = (a_lit, lit_value(&b_num, &b_stmts)) {
+                    fold!(op.apply(a, b));
+                }
                 let r = reg_base.into();
                 let a = a_num.into();
                 let b = b_num.into();
@@ -788,9 +1597,13 @@ impl Translator {
                     },
                     // Logical
                     BinaryOp::And => vec![Stmt::And([r, a, b])],
-                    BinaryOp::Nor => unimplemented!(),
+                    BinaryOp::Nor => vec![Stmt::Nor([r, a, b])],
                     BinaryOp::Or  => vec![Stmt::Or ([r, a, b])],
                     BinaryOp::Xor => vec![Stmt::Xor([r, a, b])],
+                    // Bitwise
+                    BinaryOp::Sll => vec![Stmt::Sll([r, a, b])],
+                    BinaryOp::Srl => vec![Stmt::Srl([r, a, b])],
+                    BinaryOp::Sra => vec![Stmt::Sra([r, a, b])],
                     // Relational
                     BinaryOp::Eq  => zero_variant!(r, a, b, Stmt::Seq, Stmt::Seqz),
                     BinaryOp::Ge  => zero_variant!(r, a, b, Stmt::Sge, Stmt::Sgez, Stmt::Slez),
@@ -808,9 +1621,19 @@ impl Translator {
             }
             Expr::Ternary { cond, if_t, if_f } => {
                 let reg_base = self.unwrap_reg_base(reg_base_opt);
-                let (a, a_stmts) = self.translate_num(None, cond).unwrap();
-                let (b, b_stmts) = self.translate_num(None, if_t).unwrap();
-                let (c, c_stmts) = self.translate_num(None, if_f).unwrap();
+                let (a, a_stmts) = self.translate_num(None, cond)?;
+                let (b, b_stmts) = self.translate_num(None, if_t)?;
+                let (c, c_stmts) = self.translate_num(None, if_f)?;
+                if let Some(cond) = lit_value(&a, &a_stmts) {
+                    let (n, stmts) = if cond != 0_f64 {
+                        (&b, &b_stmts)
+                    } else {
+                        (&c, &c_stmts)
+                    };
+                    if let Some(n) = lit_value(n, stmts) {
+                        fold!(n);
+                    }
+                }
                 let stmt = Stmt::Select([reg_base.into(), a.into(), b.into(), c.into()]);
                 let stmts = a_stmts
                     .into_iter()
@@ -823,11 +1646,85 @@ impl Translator {
         }
     }
 
+    // Evaluate the expression of a constant at compile time
+    fn eval_const_expr(&self, key: &str, expr: &myps::ast::Expr) -> MypsResult<f64> {
+        use myps::ast::Expr;
+
+        match expr {
+            Expr::Num(num) => self.eval_const_num(key, num),
+            Expr::Unary { op, rhs } => Ok(op.apply(self.eval_const_num(key, rhs)?)),
+            Expr::Binary { op, lhs, rhs } => {
+                let l = self.eval_const_num(key, lhs)?;
+                let r = self.eval_const_num(key, rhs)?;
+                Ok(op.apply(l, r))
+            }
+            Expr::Ternary { cond, if_t, if_f } => {
+                if self.eval_const_num(key, cond)? != 0_f64 {
+                    self.eval_const_num(key, if_t)
+                } else {
+                    self.eval_const_num(key, if_f)
+                }
+            }
+        }
+    }
+
+    // Evaluate a number of the expression of a constant at compile time, which can only be a
+    // literal, another constant or a pure math function of those
+    fn eval_const_num(&self, key: &str, num: &myps::ast::Num) -> MypsResult<f64> {
+        use myps::ast::{Arg, Func, Num, Var};
+
+        let not_const = |what: &str| Err(MypsError::def_not_const(key, what));
+        match num {
+            Num::Lit(n) => Ok(*n),
+            Num::Var(Var { key: rv_key, .. }) => match self.try_get_alias(rv_key)? {
+                Alias::Num(n) => Ok(*n),
+                _ => not_const(&format!("the variable '{}'", rv_key)),
+            },
+            Num::Expr(box expr) => self.eval_const_expr(key, expr),
+            Num::Func(box func) => {
+                let eval = |arg: &Arg| match arg {
+                    Arg::Expr(expr) => self.eval_const_expr(key, expr),
+                    Arg::Dev(..) => not_const("a device"),
+                };
+                #[rustfmt::skip]
+                let n = match func {
+                    Func::Abs  ([a   ]) => eval(a)?.abs(),
+                    Func::Acos ([a   ]) => eval(a)?.acos(),
+                    Func::Asin ([a   ]) => eval(a)?.asin(),
+                    Func::Atan ([a   ]) => eval(a)?.atan(),
+                    Func::Ceil ([a   ]) => eval(a)?.ceil(),
+                    Func::Cos  ([a   ]) => eval(a)?.cos(),
+                    Func::Exp  ([a   ]) => eval(a)?.exp(),
+                    Func::Floor([a   ]) => eval(a)?.floor(),
+                    Func::Log  ([a, b]) => eval(a)?.ln() / eval(b)?.ln(),
+                    Func::Ln   ([a   ]) => eval(a)?.ln(),
+                    Func::Max  ([a, b]) => eval(a)?.max(eval(b)?),
+                    Func::Min  ([a, b]) => eval(a)?.min(eval(b)?),
+                    Func::Round([a   ]) => eval(a)?.round(),
+                    Func::Sin  ([a   ]) => eval(a)?.sin(),
+                    Func::Sqrt ([a   ]) => eval(a)?.sqrt(),
+                    Func::Tan  ([a   ]) => eval(a)?.tan(),
+                    Func::Trunc([a   ]) => eval(a)?.trunc(),
+                    Func::Dns(..) | Func::Dse(..) => return not_const("a device"),
+                    Func::Rand(..) => return not_const("the function 'rand'"),
+                    Func::Peek(..) | Func::Pop(..) => return not_const("the stack"),
+                };
+                Ok(n)
+            }
+            Num::Call(call) => not_const(&format!("a call of '{}'", call.name)),
+            Num::DevParam { .. }
+            | Num::DevSlot { .. }
+            | Num::DevReagent { .. }
+            | Num::NetParam { .. }
+            | Num::NetName { .. } => not_const("reading a device"),
+        }
+    }
+
     fn translate_num(
         &mut self,
         reg_base_opt: Option<mips::ast::RegBase>,
         num: myps::ast::Num,
-    ) -> MipsResult<(mips::ast::Num, Vec<mips::ast::Stmt>)> {
+    ) -> MypsResult<(mips::ast::Num, Vec<mips::ast::Stmt>)> {
         use mips::ast::{Arg, Dev, Num, Reg, Stmt};
         use myps::ast::Var;
         use std::iter::once;
@@ -835,15 +1732,16 @@ impl Translator {
         match num {
             myps::ast::Num::Lit(n) => Ok((mips::ast::Num::Lit(n), Vec::new())),
             myps::ast::Num::Var(Var { key, .. }) => {
-                let alias = self.try_get_alias(&key).unwrap();
-                let num = alias.try_into().unwrap();
+                let alias = self.try_get_alias(&key)?;
+                let num = alias.try_into()?;
                 Ok((num, Vec::new()))
             }
             myps::ast::Num::Expr(box expr) => self.translate_expr(reg_base_opt, expr),
             myps::ast::Num::Func(box func) => self.translate_func(reg_base_opt, func),
+            myps::ast::Num::Call(call) => self.translate_call(reg_base_opt, call, true),
             myps::ast::Num::DevParam { dev, param } => {
                 let reg_base = self.unwrap_reg_base(reg_base_opt);
-                let (dev_base, dev_stmts) = self.translate_dev(dev).unwrap();
+                let (dev_base, dev_stmts) = self.translate_dev(dev)?;
                 let l_stmt = Stmt::L([reg_base.into(), dev_base.into(), param.into()]);
                 let stmts = dev_stmts.into_iter().chain(once(l_stmt)).collect();
                 Ok((reg_base.into(), stmts))
@@ -854,8 +1752,8 @@ impl Translator {
                 param,
             } => {
                 let reg_base = self.unwrap_reg_base(reg_base_opt);
-                let (dev_base, dev_stmts) = self.translate_dev(dev).unwrap();
-                let (slot, slot_stmts) = self.translate_num(None, slot).unwrap();
+                let (dev_base, dev_stmts) = self.translate_dev(dev)?;
+                let (slot, slot_stmts) = self.translate_num(None, slot)?;
                 let ls_stmt =
                     Stmt::Ls([reg_base.into(), dev_base.into(), slot.into(), param.into()]);
                 let stmts = dev_stmts
@@ -865,49 +1763,74 @@ impl Translator {
                     .collect();
                 Ok((reg_base.into(), stmts))
             }
-            myps::ast::Num::DevReagent {
-                dev,
-                box mode,
-                reagent,
-            } => {
+            myps::ast::Num::DevReagent { dev, mode, reagent } => {
                 let reg_base = self.unwrap_reg_base(reg_base_opt);
-                let (dev_base, dev_stmts) = self.translate_dev(dev).unwrap();
-                let (mode, mode_stmts) = self.translate_num(None, mode).unwrap();
-                let lr_stmt = Stmt::Lr([
-                    reg_base.into(),
-                    dev_base.into(),
-                    mode.into(),
-                    reagent.into(),
-                ]);
+                let (dev_base, dev_stmts) = self.translate_dev(dev)?;
+                let (mode, mode_stmts) = self.translate_mode(mode)?;
+                let lr_stmt = Stmt::Lr([reg_base.into(), dev_base.into(), mode, reagent.into()]);
                 let stmts = dev_stmts
                     .into_iter()
-                    .chain(mode_stmts.into_iter())
+                    .chain(mode_stmts)
                     .chain(once(lr_stmt))
                     .collect();
                 Ok((reg_base.into(), stmts))
             }
             myps::ast::Num::NetParam {
                 box hash,
-                box mode,
+                mode,
                 param,
             } => {
                 let reg_base = self.unwrap_reg_base(reg_base_opt);
-                let (hash, hash_stmts) = self.translate_num(None, hash).unwrap();
-                let mode = if let myps::ast::Num::Var(mut var) = mode {
-                    var.key = var.key.to_lowercase();
-                    myps::ast::Num::Var(var)
-                } else {
-                    mode
-                };
-                let (mode, mode_stmts) = self.translate_num(None, mode).unwrap();
-                let lb_stmt = Stmt::Lb([reg_base.into(), hash.into(), param.into(), mode.into()]);
+                let (hash, hash_stmts) = self.translate_num(None, hash)?;
+                let (mode, mode_stmts) = self.translate_mode(mode)?;
+                let lb_stmt = Stmt::Lb([reg_base.into(), hash.into(), param.into(), mode]);
                 let stmts = hash_stmts
                     .into_iter()
-                    .chain(mode_stmts.into_iter())
+                    .chain(mode_stmts)
                     .chain(once(lb_stmt))
                     .collect();
                 Ok((reg_base.into(), stmts))
             }
+            myps::ast::Num::NetName {
+                box hash,
+                name,
+                param,
+                mode,
+            } => {
+                let reg_base = self.unwrap_reg_base(reg_base_opt);
+                let (hash, hash_stmts) = self.translate_num(None, hash)?;
+                let name_hash = Num::Lit(constants::hash(&name) as f64);
+                let (mode, mode_stmts) = self.translate_mode(mode)?;
+                let lbn_stmt = Stmt::Lbn([
+                    reg_base.into(),
+                    hash.into(),
+                    name_hash.into(),
+                    param.into(),
+                    mode,
+                ]);
+                let stmts = hash_stmts
+                    .into_iter()
+                    .chain(mode_stmts)
+                    .chain(once(lbn_stmt))
+                    .collect();
+                Ok((reg_base.into(), stmts))
+            }
+        }
+    }
+
+    // Translate a batch or reagent mode, computing it first if it's only known at runtime
+    fn translate_mode<M: Into<mips::ast::Arg>>(
+        &mut self,
+        mode: myps::ast::Mode<M>,
+    ) -> MypsResult<(mips::ast::Arg, Vec<mips::ast::Stmt>)> {
+        use myps::ast::Mode;
+
+        match mode {
+            Mode::Lit(mode) => Ok((mode.into(), Vec::new())),
+            Mode::Num(box num) => {
+                let (num, stmts) = self.translate_num(None, num)?;
+                Ok((num.into(), stmts))
+            }
         }
     }
 
@@ -915,7 +1838,7 @@ impl Translator {
         &mut self,
         reg_base_opt: Option<mips::ast::RegBase>,
         func: myps::ast::Func,
-    ) -> MipsResult<(mips::ast::Num, Vec<mips::ast::Stmt>)> {
+    ) -> MypsResult<(mips::ast::Num, Vec<mips::ast::Stmt>)> {
         use mips::ast::{Arg, Dev, Num, Reg, Stmt};
         use myps::ast::{Func, Var};
         use std::iter::once;
@@ -926,17 +1849,32 @@ impl Translator {
                 let stmt = $mips([reg_base.into()]);
                 Ok((reg_base.into(), vec![stmt]))
             }};
-            ($self:ident, $reg_opt:ident, [$a:ident], $mips:path) => {{
+            ($self:ident, $reg_opt:ident, [$a:ident], $mips:path $(, $fold:expr)?) => {{
+                let next_index = $self.next_index;
                 let reg_base = self.unwrap_reg_base($reg_opt);
-                let (a, a_stmts) = $self.translate_arg($a).unwrap();
+                let (a, a_stmts) = $self.translate_arg($a)?;
+                $(
+                    if let Some(a) = arg_lit_value(&a, &a_stmts) {
+                        $self.next_index = next_index;
+                        return Ok((Num::Lit($fold(a)), Vec::new()));
+                    }
+                )?
                 let stmt = $mips([reg_base.into(), a.into()]);
                 let stmts = a_stmts.into_iter().chain(once(stmt)).collect();
                 Ok((reg_base.into(), stmts))
             }};
-            ($self:ident, $reg_opt:ident, [$a:ident, $b:ident], $mips:path) => {{
+            ($self:ident, $reg_opt:ident, [$a:ident, $b:ident], $mips:path $(, $fold:expr)?) => {{
+                let next_index = $self.next_index;
                 let reg_base = self.unwrap_reg_base($reg_opt);
-                let (a, a_stmts) = $self.translate_arg($a).unwrap();
-                let (b, b_stmts) = $self.translate_arg($b).unwrap();
+                let (a, a_stmts) = $self.translate_arg($a)?;
+                let (b, b_stmts) = $self.translate_arg($b)?;
+                $(
+                    let a_lit = arg_lit_value(&a, &a_stmts);
+                    if let (Some(a), Some(b)) = (a_lit, arg_lit_value(&b, &b_stmts)) {
+                        $self.next_index = next_index;
+                        return Ok((Num::Lit($fold(a, b)), Vec::new()));
+                    }
+                )?
                 let stmt = $mips([reg_base.into(), a.into(), b.into()]);
                 let stmts = a_stmts
                     .into_iter()
@@ -951,19 +1889,25 @@ impl Translator {
         match func {
             Func::Dns  ([d   ]) => translate_fun!(self, reg_base_opt, [d   ], Stmt::Sdns),
             Func::Dse  ([d   ]) => translate_fun!(self, reg_base_opt, [d   ], Stmt::Sdse),
-            Func::Abs  ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Abs),
-            Func::Acos ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Acos),
-            Func::Asin ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Asin),
-            Func::Atan ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Atan),
-            Func::Ceil ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Ceil),
-            Func::Cos  ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Cos),
-            Func::Exp  ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Exp),
-            Func::Floor([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Floor),
+            Func::Abs  ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Abs,   f64::abs),
+            Func::Acos ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Acos,  f64::acos),
+            Func::Asin ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Asin,  f64::asin),
+            Func::Atan ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Atan,  f64::atan),
+            Func::Ceil ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Ceil,  f64::ceil),
+            Func::Cos  ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Cos,   f64::cos),
+            Func::Exp  ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Exp,   f64::exp),
+            Func::Floor([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Floor, f64::floor),
             Func::Log  ([a, b]) => {
+                let next_index = self.next_index;
                 let reg_base = self.unwrap_reg_base(reg_base_opt);
                 let r = Arg::Reg(Reg::Base(reg_base));
-                let (a, a_stmts) = self.translate_arg(a).unwrap();
-                let (b, b_stmts) = self.translate_arg(b).unwrap();
+                let (a, a_stmts) = self.translate_arg(a)?;
+                let (b, b_stmts) = self.translate_arg(b)?;
+                let a_lit = arg_lit_value(&a, &a_stmts);
+                if let (Some(a), Some(b)) = (a_lit, arg_lit_value(&b, &b_stmts)) {
+                    self.next_index = next_index;
+                    return Ok((Num::Lit(a.ln() / b.ln()), Vec::new()));
+                }
                 let log_stmts = vec![
                     Stmt::Log([r.clone(), a]),
                     Stmt::Log([r.clone(), b]),
@@ -976,15 +1920,15 @@ impl Translator {
                     .collect();
                 Ok((reg_base.into(), stmts))
             },
-            Func::Ln   ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Log),
-            Func::Max  ([a, b]) => translate_fun!(self, reg_base_opt, [a, b], Stmt::Max),
-            Func::Min  ([a, b]) => translate_fun!(self, reg_base_opt, [a, b], Stmt::Min),
+            Func::Ln   ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Log,   f64::ln),
+            Func::Max  ([a, b]) => translate_fun!(self, reg_base_opt, [a, b], Stmt::Max,   f64::max),
+            Func::Min  ([a, b]) => translate_fun!(self, reg_base_opt, [a, b], Stmt::Min,   f64::min),
             Func::Rand ([    ]) => translate_fun!(self, reg_base_opt, [    ], Stmt::Rand),
-            Func::Round([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Round),
-            Func::Sin  ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Sin),
-            Func::Sqrt ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Sqrt),
-            Func::Tan  ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Tan),
-            Func::Trunc([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Trunc),
+            Func::Round([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Round, f64::round),
+            Func::Sin  ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Sin,   f64::sin),
+            Func::Sqrt ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Sqrt,  f64::sqrt),
+            Func::Tan  ([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Tan,   f64::tan),
+            Func::Trunc([a   ]) => translate_fun!(self, reg_base_opt, [a   ], Stmt::Trunc, f64::trunc),
             Func::Peek ([    ]) => translate_fun!(self, reg_base_opt, [    ], Stmt::Peek),
             Func::Pop  ([    ]) => translate_fun!(self, reg_base_opt, [    ], Stmt::Pop),
         }
@@ -993,16 +1937,16 @@ impl Translator {
     fn translate_arg(
         &mut self,
         arg: myps::ast::Arg,
-    ) -> MipsResult<(mips::ast::Arg, Vec<mips::ast::Stmt>)> {
+    ) -> MypsResult<(mips::ast::Arg, Vec<mips::ast::Stmt>)> {
         use mips::ast::Arg;
 
         match arg {
             myps::ast::Arg::Dev(dev) => {
-                let (dev_base, stmts) = self.translate_dev(dev).unwrap();
+                let (dev_base, stmts) = self.translate_dev(dev)?;
                 Ok((dev_base.into(), stmts))
             }
             myps::ast::Arg::Expr(expr) => {
-                let (num, stmts) = self.translate_expr(None, expr).unwrap();
+                let (num, stmts) = self.translate_expr(None, expr)?;
                 Ok((num.into(), stmts))
             }
         }
@@ -1011,7 +1955,7 @@ impl Translator {
     fn translate_dev(
         &mut self,
         dev: myps::ast::Dev,
-    ) -> MipsResult<(mips::ast::DevBase, Vec<mips::ast::Stmt>)> {
+    ) -> MypsResult<(mips::ast::DevBase, Vec<mips::ast::Stmt>)> {
         use mips::ast::{Dev, DevBase, DevLit, Num, Reg, RegLit};
         use myps::ast::Var;
 
@@ -1022,7 +1966,7 @@ impl Translator {
                 Ok((dev_base, Vec::new()))
             }
             myps::ast::Dev::Expr(box expr) => {
-                let (mut index_num, index_stmts) = self.translate_expr(None, expr).unwrap();
+                let (mut index_num, index_stmts) = self.translate_expr(None, expr)?;
                 index_num.set_fixed(true);
                 // if let Num::Reg(reg_base) = index_num {
                     // reg_base.set_fixed(true);
@@ -1041,7 +1985,7 @@ impl Translator {
                     Num::Reg(..) | Num::Alias(..) => {
                         let index = match index_num {
                             Num::Reg(reg_base) => reg_base.index(),
-                            Num::Alias(key) => self.try_get_reg(&key).unwrap().index(),
+                            Num::Alias(key) => self.try_get_reg(&key)?.index(),
                             Num::Lit(..) => unreachable!(),
                         };
                         let dev_lit = DevLit {
@@ -1053,7 +1997,7 @@ impl Translator {
                 }
             }
             myps::ast::Dev::Var(Var { key, .. }) => {
-                let dev_base = self.try_get_dev_base(&key).unwrap();
+                let dev_base = self.try_get_dev_base(&key)?;
                 Ok((dev_base, Vec::new()))
             }
             myps::ast::Dev::DB => {
@@ -1063,3 +2007,542 @@ impl Translator {
         }
     }
 }
+
+/// Options of [`compile`].
+#[derive(Clone, Debug)]
+pub struct CompileOptions {
+    /// The optimizations to run, or `None` to emit the translated program as is
+    pub optimize: Option<OptimizationConfig>,
+    /// The name of the source file, for diagnostics
+    pub file: Option<String>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            optimize: Some(OptimizationConfig::default()),
+            file: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CompileStats {
+    /// The number of lines of the translated program
+    pub lines_translated: usize,
+    /// The number of lines of the final program
+    pub lines_final: usize,
+    pub optimization: OptimizationStats,
+}
+
+/// The result of [`compile`].
+#[derive(Debug, Default)]
+pub struct CompileOutput {
+    /// The final MIPS source (empty if there are diagnostics)
+    pub text: String,
+    /// The lexed myps program (with its imports inlined)
+    pub program: Option<myps::ast::Item>,
+    /// The translated program, before optimization
+    pub mips: Option<Mips>,
+    pub diagnostics: Vec<MypsError>,
+    /// The (1-based) source line of each line of the final MIPS, if known
+    pub source_map: Vec<Option<usize>>,
+    pub stats: CompileStats,
+}
+
+impl CompileOutput {
+    pub fn is_ok(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+/// Compile a myps source to MIPS: lex, translate and (optionally) optimize it.
+///
+/// Errors don't stop at the first failure where possible; all of them are reported as
+/// diagnostics, located in `options.file` if given.
+pub fn compile(source: &str, options: &CompileOptions) -> CompileOutput {
+    try_compile(source, options).unwrap_or_else(|errors| {
+        let diagnostics = errors
+            .into_iter()
+            .map(|e| match &options.file {
+                Some(file) => e.in_file(file),
+                None => e,
+            })
+            .collect();
+        CompileOutput {
+            diagnostics,
+            ..CompileOutput::default()
+        }
+    })
+}
+
+fn try_compile(source: &str, options: &CompileOptions) -> Result<CompileOutput, Vec<MypsError>> {
+    // Imports are relative to the source file, if there's one
+    let program = match &options.file {
+        Some(file) => myps::lexer::lex_source(source.to_owned(), Path::new(file))?,
+        None => myps::lexer::lex_string(source.to_owned())?,
+    };
+    let lines = Translator::default()
+        .translate_item(program.clone())
+        .map_err(|e| vec![e.with_source(source)])?;
+    let mips = Mips::default_with_lines(lines)
+        .map_err(|errors| errors.into_iter().map(MypsError::from).collect::<Vec<_>>())?;
+
+    let (output_mips, optimization) = match &options.optimize {
+        Some(conf) => mips
+            .optimize_with_stats(conf.clone())
+            .map_err(|errors| errors.into_iter().map(MypsError::from).collect::<Vec<_>>())?,
+        None => (mips.clone(), OptimizationStats::default()),
+    };
+    let minimize_size = matches!(&options.optimize, Some(conf) if conf.minimize_size);
+    let text = if minimize_size {
+        output_mips.emit_min()
+    } else {
+        output_mips.emit()
+    };
+    let source_map = output_mips
+        .lines
+        .iter()
+        .map(|line| line.source_line)
+        .collect();
+    let stats = CompileStats {
+        lines_translated: mips.lines.len(),
+        lines_final: output_mips.lines.len(),
+        optimization,
+    };
+    Ok(CompileOutput {
+        text,
+        program: Some(program),
+        mips: Some(mips),
+        diagnostics: Vec::new(),
+        source_map,
+        stats,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The translated (unoptimized) MIPS of a source
+    fn translate(source: &str) -> String {
+        let options = CompileOptions {
+            optimize: None,
+            file: None,
+        };
+        let output = compile(source, &options);
+        assert!(output.is_ok(), "{:?}", output.diagnostics);
+        output.text
+    }
+
+    // The message of the only diagnostic of compiling a source
+    fn error(source: &str) -> String {
+        let output = compile(source, &CompileOptions::default());
+        assert_eq!(output.diagnostics.len(), 1, "{:?}", output.diagnostics);
+        output.diagnostics[0].to_string()
+    }
+
+    #[test]
+    fn compile_output() {
+        let source = "# Copy a setting\nx = d0.Setting\n\nd1.Setting = x + 1\n";
+        let output = compile(source, &CompileOptions::default());
+        assert!(output.is_ok(), "{:?}", output.diagnostics);
+        let expected = "l r0 d0 Setting\nadd r0 r0 1\ns d1 Setting r0\n";
+        assert_eq!(output.text, expected);
+        assert_eq!(output.source_map, [Some(2), Some(4), Some(4)]);
+        // The comment and empty line are translated, then removed
+        let stats = &output.stats;
+        assert_eq!((stats.lines_translated, stats.lines_final), (5, 3));
+        assert_eq!(stats.optimization.total_lines_removed(), 2);
+        assert_eq!(stats.optimization.passes[0].registers_saved, 1);
+    }
+
+    #[test]
+    fn compile_diagnostics_in_file() {
+        let options = CompileOptions {
+            file: Some("main.myps".to_owned()),
+            ..CompileOptions::default()
+        };
+        // Errors of lexing and of translating
+        for source in ["x = $\n", "d0.On = 1\nd0.On = y\n"] {
+            let output = compile(source, &options);
+            assert_eq!(output.diagnostics.len(), 1, "{:?}", output.diagnostics);
+            let span = output.diagnostics[0].span().unwrap();
+            assert_eq!(span.file.as_deref(), Some("main.myps"));
+            assert_eq!(span.line, source.lines().count());
+            assert!(output.text.is_empty() && output.source_map.is_empty());
+        }
+    }
+
+    #[test]
+    fn fold_defs() {
+        let text = translate("def c = 4\ndef b = 10\nd0.Setting = c + b\nd0.On = -c\n");
+        assert_eq!(text, "s d0 Setting 14\ns d0 On -4\n");
+    }
+
+    #[test]
+    fn fold_defs_with_vars() {
+        // Only the constant part of an expression with a variable is folded
+        let text = translate("def c = 4\nx = d1.Setting\nd0.Setting = c * 2 + x\n");
+        assert_eq!(text, "l r0 d1 Setting\nadd r1 8 r0\ns d0 Setting r1\n");
+    }
+
+    #[test]
+    fn fold_nested() {
+        // Operations are folded from the inside out, including pure functions
+        let source = "def c = 4\nd0.Setting = (1 + 2) * d1.Setting + max(c, sqrt(c)) * 2\n";
+        let expected = "l r2 d1 Setting\nmul r1 3 r2\nadd r0 r1 8\ns d0 Setting r0\n";
+        assert_eq!(translate(source), expected);
+        let text = translate("def c = 4\nd0.On = (c > 2) ? 1 : d1.On\nd0.Setting = abs(-c)\n");
+        assert_eq!(text, "s d0 On 1\ns d0 Setting 4\n");
+    }
+
+    #[test]
+    fn net_name_hashes() {
+        // The names are hashed with CRC-32 at compile time
+        let source = "\
+def h = -1252983604
+x = h.all[\"Zone A\"].Temperature.max
+h.all[\"East Array\"].Horizontal = x
+";
+        let expected = "\
+lbn r0 -1252983604 101693169 Temperature Maximum
+sbn -1252983604 1270505638 Horizontal r0
+";
+        assert_eq!(translate(source), expected);
+    }
+
+    #[test]
+    fn slot_writes() {
+        let source = "\
+i = d1.Setting
+d0[i].On = 1
+5.all[2].Lock = i
+";
+        let expected = "\
+l r0 d1 Setting
+ss d0 r0 On 1
+sbs 5 2 Lock r0
+";
+        assert_eq!(translate(source), expected);
+        // A slot logic type isn't a logic type of a device
+        let expected = "'Occupied' is a slot logic type, expected a logic type";
+        for source in ["d0.Occupied = 1\n", "5.all.Occupied = 1\n"] {
+            assert_eq!(error(source).lines().next(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn self_assignments() {
+        let source = "\
+def h = 12
+d0.Setting += 1
+db.Setting *= 2
+h.all.Setting.Maximum -= 5
+";
+        let expected = "\
+l r0 d0 Setting
+add r0 r0 1
+s d0 Setting r0
+l r1 db Setting
+mul r1 r1 2
+s db Setting r1
+lb r2 12 Setting Maximum
+sub r2 r2 5
+sb 12 Setting r2
+";
+        assert_eq!(translate(source), expected);
+        let message = error("def h = 12\nh.all.Setting -= 5\n");
+        assert!(message.starts_with("Compound assignment to a batch parameter needs a batch mode"));
+    }
+
+    #[test]
+    fn break_continue_loop() {
+        let source = "\
+loop:
+    x = d0.Setting
+    if x > 1:
+        break
+    if x < 0:
+        continue
+    d0.On = x
+d0.On = 0
+";
+        let expected = "\
+nextLoop0:
+l r0 d0 Setting
+brle r0 1 2
+j endLoop0
+brgez r0 2
+j nextLoop0
+s d0 On r0
+jr -6
+endLoop0:
+s d0 On 0
+";
+        assert_eq!(translate(source), expected);
+    }
+
+    #[test]
+    fn break_while() {
+        let expected = "l r0 d0 On\nbreqz r0 3\nj endLoop0\njr -3\nendLoop0:\n";
+        assert_eq!(translate("while d0.On:\n    break\n"), expected);
+    }
+
+    #[test]
+    fn continue_for_in_fn() {
+        // Continuing a for loop still steps its variable
+        let source = "\
+fn f():
+    for i in (0:3):
+        if i == 1:
+            continue
+        d0.Setting = i
+f()
+";
+        let expected = "\
+jr 11
+f:
+move r1 0
+brge r1 3 7
+brne r1 1 2
+j nextLoop0
+s d0 Setting r1
+nextLoop0:
+add r1 r1 1
+jr -6
+j ra
+jal f
+";
+        assert_eq!(translate(source), expected);
+    }
+
+    #[test]
+    fn break_loop_of_macro() {
+        // A loop expanded from a macro has its own tags, inside those of the loop of the call
+        let source = "\
+macro m():
+    loop:
+        if d1.On:
+            break
+loop:
+    m()
+    if d0.On:
+        break
+";
+        let expected = "\
+l r1 d1 On
+breqz r1 2
+j endLoop1
+jr -3
+endLoop1:
+l r1 d0 On
+breqz r1 2
+j endLoop0
+jr -8
+endLoop0:
+";
+        assert_eq!(translate(source), expected);
+    }
+
+    #[test]
+    fn break_outside_loop() {
+        let output = compile("break\n", &CompileOptions::default());
+        assert_eq!(output.diagnostics.len(), 1, "{:?}", output.diagnostics);
+        let message = output.diagnostics[0].to_string();
+        assert!(message.contains("'break' outside of a loop"), "{}", message);
+    }
+
+    #[test]
+    fn continue_in_macro_outside_loop() {
+        // The body of a macro is checked where it's defined, not where it's expanded
+        let source = "macro m():\n    continue\nloop:\n    m()\n";
+        let output = compile(source, &CompileOptions::default());
+        assert_eq!(output.diagnostics.len(), 1, "{:?}", output.diagnostics);
+        let message = output.diagnostics[0].to_string();
+        let expected = "'continue' outside of a loop";
+        assert!(message.contains(expected), "{}", message);
+    }
+
+    #[test]
+    fn parallel_swap() {
+        // The r-values are all read before any l-value is written
+        let source = "\
+a = d0.Setting
+b = d1.Setting
+a, b = b, a
+d0.Setting = a
+d1.Setting = b
+";
+        let expected = "\
+l r0 d0 Setting
+l r1 d1 Setting
+move r2 r0
+move r0 r1
+move r1 r2
+s d0 Setting r0
+s d1 Setting r1
+";
+        assert_eq!(translate(source), expected);
+    }
+
+    #[test]
+    fn parallel_rotate() {
+        let source = "\
+a = d0.Setting
+b = d1.Setting
+c = d2.Setting
+a, b, c = b, c, a
+";
+        let expected = "\
+l r0 d0 Setting
+l r1 d1 Setting
+l r2 d2 Setting
+move r3 r0
+move r0 r1
+move r1 r2
+move r2 r3
+";
+        assert_eq!(translate(source), expected);
+    }
+
+    #[test]
+    fn parallel_exprs() {
+        let source = "\
+a = d0.Setting
+b = d1.Setting
+a, b = a + b, a - b
+d0.Setting, a = a, 5
+";
+        let expected = "\
+l r0 d0 Setting
+l r1 d1 Setting
+add r2 r0 r1
+sub r3 r0 r1
+move r0 r2
+move r1 r3
+s d0 Setting r0
+move r0 5
+";
+        assert_eq!(translate(source), expected);
+    }
+
+    #[test]
+    fn parallel_wrong_num() {
+        let output = compile("a, b = 1\n", &CompileOptions::default());
+        assert_eq!(output.diagnostics.len(), 1, "{:?}", output.diagnostics);
+        let message = output.diagnostics[0].to_string();
+        let expected = "Cannot assign 1 r-values to 2 l-values";
+        assert!(message.contains(expected), "{}", message);
+    }
+
+    #[test]
+    fn call_stmt() {
+        let source = "fn f(a):\n    d0.Setting = a\nf(3)\n";
+        let expected = "jr 4\nf:\ns d0 Setting r0\nj ra\nmove r0 3\njal f\n";
+        assert_eq!(translate(source), expected);
+    }
+
+    #[test]
+    fn call_value() {
+        // The returned value is moved out of the return register of the function
+        let source = "\
+fn g(a):
+    return a * 2
+x = g(d0.Setting)
+d1.Setting = x + 1
+";
+        let expected = "\
+jr 4
+g:
+mul r1 r0 2
+j ra
+l r3 d0 Setting
+move r0 r3
+jal g
+move r2 r1
+add r4 r2 1
+s d1 Setting r4
+";
+        assert_eq!(translate(source), expected);
+    }
+
+    #[test]
+    fn call_nested() {
+        // A call within a function saves and restores the return address of the function
+        let source = "\
+fn inner(a):
+    return a + 1
+fn outer(b):
+    return inner(b) * 2
+d0.Setting = outer(d1.Setting)
+";
+        let expected = "\
+jr 4
+inner:
+add r1 r0 1
+j ra
+jr 9
+outer:
+move r0 r2
+push ra
+jal inner
+pop ra
+move r4 r1
+mul r3 r4 2
+j ra
+l r5 d1 Setting
+move r2 r5
+jal outer
+move r6 r3
+s d0 Setting r6
+";
+        assert_eq!(translate(source), expected);
+    }
+
+    #[test]
+    fn call_registers_optimized() {
+        // A register live across a call isn't allocated to a register of the function
+        let source = "\
+fn f(a):
+    return a * 2
+x = d0.Setting
+y = f(1)
+d1.Setting = x + y
+";
+        let expected = "\
+jr 3
+mul r1 r0 2
+j ra
+l r2 d0 Setting
+move r0 1
+jal 1
+move r0 r1
+add r0 r2 r0
+s d1 Setting r0
+";
+        let output = compile(source, &CompileOptions::default());
+        assert!(output.is_ok(), "{:?}", output.diagnostics);
+        assert_eq!(output.text, expected);
+    }
+
+    #[test]
+    fn call_recursive() {
+        let message = error("fn f(a):\n    f(a)\nf(1)\n");
+        assert!(message.contains("A recursive call"), "{}", message);
+    }
+
+    #[test]
+    fn call_wrong_num() {
+        let message = error("fn f(a):\n    d0.On = a\nf(1, 2)\n");
+        let expected = "Function 'f' expects 1 arguments, found 2";
+        assert!(message.contains(expected), "{}", message);
+    }
+
+    #[test]
+    fn call_no_value() {
+        let message = error("fn f(a):\n    d0.On = a\nx = f(1)\nd0.Setting = x\n");
+        let expected = "Function 'f' doesn't return a value";
+        assert!(message.contains(expected), "{}", message);
+    }
+}
@@ -1,84 +1,180 @@
-use translator::Translator;
+use std::io::{Read, Write};
 
-fn main() {
-    use mips::{Mips, OptimizationConfig};
-
-    let myps_path = std::env::args().skip(1).next().unwrap();
-    let program_item = myps::lexer::lex_file(&myps_path).unwrap();
-    // println!("{:#?}", program_item);
-
-    // println!("================================================================================");
-    let mut translator = Translator::default();
-    let lines = translator.translate_item(program_item).unwrap();
-    let w = (lines.len() as f64 - 1.0).log10().floor().max(0_f64) as usize + 1;
-    // for (i, line) in lines.iter().enumerate() {
-    //     println!("{:>w$}: {:?}", i, line, w = w);
-    // }
-    for (i, line) in lines.iter().enumerate() {
-        println!("{:>w$}: {}", i, line, w = w);
-    }
-    // println!("{:#?}", scopes);
+use mips::cli::Failure;
+use mips::{Mips, OptimizationConfig};
+use myps::ast::Item;
+use translator::CompileOptions;
 
-    println!("================================================================================");
-    let mips = Mips::default_with_lines(lines).unwrap();
-    let w = (mips.lines.len() as f64 - 1.0).log10().floor().max(0_f64) as usize + 1;
-    for (i, line) in mips.lines.iter().enumerate() {
-        println!("{:>w$}: {:?}", i, line, w = w);
-    }
-    println!("--------------------------------------------------------------------------------");
-    for (i, line) in mips.lines.iter().enumerate() {
-        println!("{:>w$}: {}", i, line, w = w);
-    }
-    println!("{}", mips.interference_graph());
-    for (i, (index, (s, e))) in mips.analyze_lifetimes().iter().enumerate() {
-        println!("{}: {} ({},{})", i, index, s, e);
+fn usage() -> String {
+    format!(
+        "\
+usage: translator [options] [path]
+
+Compile a myps program to MIPS. The program is read from stdin if there's no path or the path
+is `-`.
+
+options:
+    -o, --output <path>         write the output to a file rather than stdout
+    --emit <kinds>              what to output, as a comma-separated list of:
+                                ast        the parsed myps program
+                                mips-raw   the translated MIPS, before optimization
+                                mips-opt   the optimized MIPS (the default)
+                                lifetimes  the register lifetimes of the translated MIPS
+    --no-optimize               don't optimize
+{}    -h, --help                  print this message",
+        mips::cli::optimization_usage()
+    )
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Emit {
+    Ast,
+    MipsRaw,
+    MipsOpt,
+    Lifetimes,
+}
+
+impl Emit {
+    const ALL: [Emit; 4] = [Emit::Ast, Emit::MipsRaw, Emit::MipsOpt, Emit::Lifetimes];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Ast => "ast",
+            Self::MipsRaw => "mips-raw",
+            Self::MipsOpt => "mips-opt",
+            Self::Lifetimes => "lifetimes",
+        }
     }
-    // println!("SCOPES {:?}", mips.scopes);
+}
 
-    println!("================================================================================");
-    println!("OPTIMIZE");
-    #[rustfmt::skip]
-        let mips = mips
-        .optimize(OptimizationConfig {
-            optimize_registers: true,
-            // optimize_registers: false,
+impl std::str::FromStr for Emit {
+    type Err = String;
 
-            remove_comments: true,
-            // remove_comments: false,
+    fn from_str(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format!("unknown emit kind '{}'", s))
+    }
+}
 
-            remove_empty: true,
-            // remove_empty: false,
+#[derive(Debug)]
+struct Args {
+    help: bool,
+    path: Option<String>,
+    output: Option<String>,
+    emits: Vec<Emit>,
+    optimize: Option<OptimizationConfig>,
+}
 
-            remove_empty_comments: true,
-            // remove_empty_comments: false,
+impl Args {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, Failure> {
+        let mut help = false;
+        let mut path = None;
+        let mut output = None;
+        let mut emits = None;
+        let mut optimize = true;
 
-            remove_reg_aliases: true,
-            // remove_reg_aliases: false,
+        let conf = mips::cli::parse_args(args, |arg, value| {
+            match arg {
+                "-h" | "--help" => help = true,
+                "-o" | "--output" => output = Some(value()?),
+                "--emit" => {
+                    let kinds = value()?
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<Vec<Emit>, _>>()
+                        .map_err(Failure::usage)?;
+                    emits = Some(kinds);
+                }
+                "--no-optimize" => optimize = false,
+                "-" => path = Some(arg.to_owned()),
+                _ if arg.starts_with('-') => {
+                    return Err(Failure::usage(format!("unknown option '{}'", arg)))
+                }
+                _ if path.is_none() => path = Some(arg.to_owned()),
+                _ => return Err(Failure::usage(format!("unexpected argument '{}'", arg))),
+            }
+            Ok(())
+        })?;
+        Ok(Self {
+            help,
+            path: path.filter(|path| path != "-"),
+            output,
+            emits: emits.unwrap_or_else(|| vec![Emit::MipsOpt]),
+            optimize: optimize.then_some(conf),
+        })
+    }
+}
 
-            remove_dev_aliases: true,
-            // remove_dev_aliases: false,
+// Emit a kind of output of a compilation, from its lexed and translated programs and final text
+fn emit(kind: Emit, program: &Item, mips: &Mips, text: &str) -> String {
+    match kind {
+        Emit::Ast => format!("{:#?}\n", program),
+        Emit::MipsRaw => mips.emit(),
+        Emit::MipsOpt => text.to_owned(),
+        Emit::Lifetimes => mips
+            .analyze_lifetimes()
+            .iter()
+            .enumerate()
+            .map(|(i, (index, (s, e)))| format!("{}: {} ({},{})\n", i, index, s, e))
+            .collect(),
+    }
+}
 
-            remove_defines: true,
-            // remove_defines: false,
+fn run() -> Result<(), Failure> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    if args.help {
+        println!("{}", usage());
+        return Ok(());
+    }
 
-            remove_tags: true,
-            // remove_tags: false,
-        },
-        )
-        .unwrap();
+    let source = match &args.path {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| Failure::new(format!("couldn't read {}: {}", path, e)))?,
+        None => {
+            let mut source = String::new();
+            std::io::stdin()
+                .read_to_string(&mut source)
+                .map_err(|e| Failure::new(format!("couldn't read stdin: {}", e)))?;
+            source
+        }
+    };
+    let options = CompileOptions {
+        optimize: args.optimize.clone(),
+        file: Some(args.path.clone().unwrap_or_else(|| "<stdin>".to_owned())),
+    };
+    let output = translator::compile(&source, &options);
+    if !output.is_ok() {
+        return Err(Failure::all(output.diagnostics));
+    }
+    // A successful compilation has both the lexed and the translated program
+    let (program, mips) = match (&output.program, &output.mips) {
+        (Some(program), Some(mips)) => (program, mips),
+        _ => return Err(Failure::new("the compilation produced no program")),
+    };
 
-    println!("{}", mips.interference_graph());
-    let w = (mips.lines.len() as f64 - 1.0).log10().floor().max(0_f64) as usize + 1;
-    for (i, line) in mips.lines.iter().enumerate() {
-        println!("{:>w$}: {:?}", i, line, w = w);
+    // Sections are only headed (by a comment) when there are several of them
+    let mut text = String::new();
+    for kind in args.emits.iter() {
+        if args.emits.len() > 1 {
+            text.push_str(&format!("# {}\n", kind.name()));
+        }
+        text.push_str(&emit(*kind, program, mips, &output.text));
+    }
+    match &args.output {
+        Some(path) => std::fs::write(path, text)
+            .map_err(|e| Failure::new(format!("couldn't write {}: {}", path, e))),
+        None => std::io::stdout()
+            .write_all(text.as_bytes())
+            .map_err(Failure::new),
     }
-    println!("--------------------------------------------------------------------------------");
-    for (_i, line) in mips.lines.iter().enumerate() {
-        println!("{:>w$}: {}", _i, line, w = w);
-        // println!("{}", line);
+}
+
+fn main() {
+    if let Err(failure) = run() {
+        eprintln!("{}", failure);
+        std::process::exit(failure.exit_code());
     }
-    // for (i, (index, (s, e))) in mips.analyze_lifetimes().iter().enumerate() {
-    //     println!("{}: {} ({},{})", i, index, s, e);
-    // }
-    // println!("SCOPES {:?}", mips.scopes);
 }